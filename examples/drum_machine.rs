@@ -1,48 +1,32 @@
 use std::ops::RangeInclusive;
 
-use bevy::{
-    ecs::{entity::EntityHashMap, system::EntityCommands},
-    prelude::*,
-};
+use bevy::{ecs::system::EntityCommands, prelude::*};
 use bevy_egui::{
     EguiContextPass, EguiContexts, EguiPlugin,
     egui::{self, Pos2, Rgba, Stroke},
 };
 use bevy_mod_kira::{
-    KiraContext, KiraPlaySoundEvent, KiraPlugin, KiraStaticSoundAsset, KiraStaticSoundHandle,
-    KiraTrackHandle,
+    KiraFilter, KiraModulatedParameter, KiraParameterBinding, KiraParameterCurve, KiraPlugin,
+    KiraReverb, KiraSequencer, KiraSequencerTrack, KiraStaticSoundHandle, KiraStep, KiraTempo,
+    KiraVolumeControl,
 };
 use egui::{Color32, Id, RichText, Sense};
 use egui_extras::{Size, StripBuilder};
-use kira::{
-    Decibels, Tween, Tweenable,
-    clock::ClockHandle,
-    effect::{
-        filter::{FilterBuilder, FilterHandle},
-        reverb::{ReverbBuilder, ReverbHandle},
-    },
-    sound::static_sound::StaticSoundSettings,
-    track::{TrackBuilder, TrackHandle},
-};
+use kira::{Decibels, Mix};
 
 mod color_utils;
 use color_utils::*;
 
 const BPM: f64 = 90.0;
-const BPS: f64 = BPM / 60.0;
-const STEP_PER_BEAT: usize = 4;
-const STEPS: usize = STEP_PER_BEAT * 4;
-const STEP_PER_SEC: f64 = BPS * STEP_PER_BEAT as f64;
+const BEATS_PER_BAR: u32 = 4;
+const STEP_PER_BEAT: u32 = 4;
+const STEPS: usize = (STEP_PER_BEAT * BEATS_PER_BAR) as usize;
 
 const CHANNEL_ROW_HEIGHT: f32 = 64.0;
 const CHANNEL_UI_SIZES: [f32; 7] = [64.0, 18.0, 18.0, 128.0, 128.0, 128.0, 128.0];
 const MACHINE_H_PADDING: f32 = 32.0;
 const MACHINE_V_PADDING: f32 = 12.0;
 
-fn steps_per_sec(bpm: f64) -> f64 {
-    bpm / 60.0 * STEP_PER_BEAT as f64
-}
-
 // 16 bit boolean constants are a convienient way to represent 16 step patterns when defining
 // defaults. But for use in the UI it's more convienient to hold them as an array of bools in the
 // component so click handlers can just be given one &mut bool to flip.
@@ -52,28 +36,21 @@ const DEFAULT_HAT: DefaultPattern = DefaultPattern(0b0010_1010_1010_1111);
 const DEFAULT_SNARE: DefaultPattern = DefaultPattern(0b0000_1000_0000_1000);
 const DEFAULT_HIT: DefaultPattern = DefaultPattern(0b0010_0000_1101_0101);
 
-impl From<DefaultPattern> for DrumPattern {
-    fn from(p: DefaultPattern) -> Self {
-        let p = p.0.reverse_bits();
-        let mut steps = [false; STEPS];
-        for (i, item) in steps.iter_mut().enumerate() {
-            *item = (p & (1 << i)) != 0;
-        }
-        Self { steps }
+impl DefaultPattern {
+    fn steps(&self) -> Vec<KiraStep> {
+        let p = self.0.reverse_bits();
+        (0..STEPS)
+            .map(|i| {
+                if (p & (1 << i)) != 0 {
+                    KiraStep::on()
+                } else {
+                    KiraStep::off()
+                }
+            })
+            .collect()
     }
 }
 
-#[derive(Component, Default)]
-struct DrumPattern {
-    steps: [bool; STEPS],
-}
-
-#[derive(Component)]
-struct Bpm(f64);
-
-#[derive(Component)]
-struct MainClock(ClockHandle);
-
 #[derive(Component, Debug)]
 struct ChannelInfo {
     name: String,
@@ -116,19 +93,10 @@ pub fn main() {
             },
         ))
         .add_systems(Startup, setup_sys)
-        .add_systems(Update, (playback_sys, apply_levels_sys))
         .add_systems(EguiContextPass, ui_sys)
         .run();
 }
 
-#[derive(Component)]
-struct TrackReverb(ReverbHandle);
-
-#[derive(Component)]
-// We'll store a float for our filter cutoff setting so it's easy to map to an egui slider and then
-// apply in the apply_levels_sys.
-struct MainFilter(FilterHandle, f32);
-
 #[derive(Component)]
 struct DrumMachine; // Tag component
 
@@ -136,27 +104,22 @@ struct DrumMachine; // Tag component
 // Systems
 //
 
-fn setup_sys(mut commands: Commands, mut kira: NonSendMut<KiraContext>, loader: Res<AssetServer>) {
+fn setup_sys(mut commands: Commands, loader: Res<AssetServer>) {
     // Create a top level entity to hold settings relevant to playback.
     let mut drum_machine = commands.spawn(DrumMachine);
-    drum_machine.insert(Bpm(BPM));
-    // This tells Kira to add a new clock and associate it with the drum machine entity.
-    // Clock handles will be added to the KiraClocks component on that entity.
-    let mut clock_handle = kira
-        .add_clock(kira::clock::ClockSpeed::TicksPerSecond(STEP_PER_SEC))
-        .expect("Failed to create clock.");
-    clock_handle.start();
-    drum_machine.insert(MainClock(clock_handle));
-
-    // Create a track to hold the filter effect we'll route every channel's individual track through
-    // this one shared filter.
-    let filter = FilterBuilder::new().cutoff(440.0);
-    let mut filter_track_builder = TrackBuilder::new();
-    let filter_handle = filter_track_builder.add_effect(filter);
-    let mut filter_track_handle = kira
-        .add_track(filter_track_builder)
-        .expect("Failed to create filter track.");
-    drum_machine.insert(MainFilter(filter_handle, 0.5));
+    // This tells the KiraPlugin to add a new clock and associate it with the drum machine entity.
+    // The clock ticks once per step (a 16th note) and its speed follows the tempo's bpm. The clock
+    // handle will be added in a KiraClockHandle component on that entity.
+    drum_machine.insert(KiraTempo::new(BPM, BEATS_PER_BAR, STEP_PER_BEAT));
+
+    // The KiraPlugin creates a track holding a filter effect for the drum machine entity. Every
+    // channel is a child of the drum machine so their tracks are routed through this one shared
+    // filter. Changes to the KiraFilter component are applied to the effect.
+    drum_machine.insert(KiraFilter {
+        cutoff: 440.0,
+        mix: Mix(0.5),
+        ..default()
+    });
 
     add_instrument_channel(
         "kick.ogg",
@@ -165,7 +128,6 @@ fn setup_sys(mut commands: Commands, mut kira: NonSendMut<KiraContext>, loader:
         false,
         &mut drum_machine,
         &loader,
-        &mut filter_track_handle,
     );
     add_instrument_channel(
         "hat.ogg",
@@ -174,7 +136,6 @@ fn setup_sys(mut commands: Commands, mut kira: NonSendMut<KiraContext>, loader:
         false,
         &mut drum_machine,
         &loader,
-        &mut filter_track_handle,
     );
     add_instrument_channel(
         "snare.ogg",
@@ -183,7 +144,6 @@ fn setup_sys(mut commands: Commands, mut kira: NonSendMut<KiraContext>, loader:
         false,
         &mut drum_machine,
         &loader,
-        &mut filter_track_handle,
     );
     add_instrument_channel(
         "hit.ogg",
@@ -192,105 +152,19 @@ fn setup_sys(mut commands: Commands, mut kira: NonSendMut<KiraContext>, loader:
         true,
         &mut drum_machine,
         &loader,
-        &mut filter_track_handle,
     );
-
-    drum_machine.insert(KiraTrackHandle(filter_track_handle));
-}
-
-#[derive(Default)]
-struct LastTicks(EntityHashMap<u64>);
-
-fn playback_sys(
-    assets: Res<Assets<KiraStaticSoundAsset>>,
-    channels: Query<(Entity, &KiraStaticSoundHandle, &DrumPattern)>,
-    clock: Query<&MainClock>,
-    mut ev_play: EventWriter<KiraPlaySoundEvent>,
-    mut last_ticks: Local<LastTicks>,
-) -> Result<(), BevyError> {
-    for (chan_id, sound, pattern) in channels.iter() {
-        let clock = clock.single()?;
-        let clock = &clock.0;
-        let clock_ticks = clock.time().ticks;
-        let last_tick = last_ticks.0.get(&chan_id).copied().unwrap_or(u64::MAX);
-        if clock_ticks == last_tick {
-            continue;
-        }
-        let last_tick = last_tick.min(clock_ticks);
-        if (clock_ticks - last_tick) > 1 {
-            // Playback system is not running at the same speed as the clock. Sometimes this
-            // system can miss ticks. This seems to happen often when backgrounding the app.
-            // Since this is a demo it's not imperative that every beat is scheduled but if this
-            // was a real instrument we could work around this by queueing up more than one tick
-            // in advance. That adds complexity around making sure we don't double schedule
-            // a step so we'll just warn for this demo. In practice I haven't seen it miss
-            // a tick when the app is forgrounded.
-            warn!("Missed a tick! cur: {} last: {}", clock_ticks, last_tick);
-        }
-        last_ticks.0.insert(chan_id, clock_ticks);
-        let next_play_step = clock_ticks as usize % STEPS;
-        if pattern.steps[next_play_step] {
-            if let Some(sound_asset) = assets.get(&sound.0) {
-                let sound = sound_asset.sound.0.with_settings(StaticSoundSettings {
-                    // We calculate next_play_step as the the step at current clock time, but we
-                    // want to start the sound right at precise tick so every sound will be
-                    // triggered at a 1 tick offset.
-                    start_time: (clock.time() + 1).into(),
-                    ..Default::default()
-                });
-
-                // When we write the play sound event we pass the entity id for the channel. This
-                // instructs the KiraPlugin to associate the playing sound handle with this entity by
-                // inserting a KiraActiveSounds component. That component can be used to adjust various
-                // aspects of the playing sound at runtime. It will automatically be removed when all
-                // sounds associated with the entity have finished playing.
-                ev_play.write(KiraPlaySoundEvent::new(chan_id, Some(chan_id), sound));
-            } else {
-                warn!(
-                    "Sound asset not found for handle: {:?} it may still be loading.",
-                    sound.0
-                );
-            }
-        }
-    }
-    Ok(())
-}
-
-fn apply_levels_sys(
-    mut channels: Query<
-        (&ChannelInfo, &mut KiraTrackHandle, &mut TrackReverb),
-        Changed<ChannelInfo>,
-    >,
-    mut filter: Query<&mut MainFilter>,
-) {
-    for (info, mut track, mut reverb) in channels.iter_mut() {
-        println!("Change...");
-        let amplitude = if info.muted { 0.0 } else { info.volume as f64 };
-        let volume = <kira::Decibels as Tweenable>::interpolate(
-            Decibels::SILENCE,
-            Decibels::IDENTITY,
-            amplitude,
-        );
-        track.0.set_volume(volume, Tween::default());
-        reverb.0.set_mix(info.reverb, Tween::default());
-    }
-    for mut filter in filter.iter_mut() {
-        let value = filter.1;
-        filter.0.set_mix(value, Tween::default());
-    }
 }
 
 fn ui_sys(
     mut ctx: EguiContexts,
-    mut clock: Query<&mut MainClock>,
     channel_ids: Query<&Children, With<DrumMachine>>,
-    channels: Query<(Entity, &mut DrumPattern)>,
+    channels: Query<(Entity, &mut KiraSequencer)>,
     chan_mute: Query<&mut ChannelInfo>,
-    mut bpm: Query<&mut Bpm>,
-    mut filter: Query<&mut MainFilter>,
+    mut tempo: Query<&mut KiraTempo>,
+    mut filter: Query<&mut KiraFilter>,
 ) -> Result<(), BevyError> {
-    let clock = &mut clock.single_mut()?.0;
-    let mut bpm = bpm.single_mut()?;
+    let mut tempo = tempo.single_mut()?;
+    let mut bpm = tempo.bpm;
     let mut filter = filter.single_mut()?;
     let mut machine_ui_res = Ok(());
     let ctx = ctx.try_ctx_mut();
@@ -313,10 +187,6 @@ fn ui_sys(
             .horizontal(|mut strip| {
                 strip.empty();
                 strip.cell(|ui| {
-                    clock.set_speed(
-                        kira::clock::ClockSpeed::TicksPerSecond(steps_per_sec(bpm.0)),
-                        Tween::default(),
-                    );
                     machine_ui_res =
                         machine_ui(ui, &mut bpm, &mut filter, channel_ids, channels, chan_mute);
                 });
@@ -324,6 +194,11 @@ fn ui_sys(
             });
     });
     machine_ui_res?;
+    // Only write the tempo when the slider moved, the KiraPlugin applies tempo changes to the
+    // clock speed.
+    if bpm != tempo.bpm {
+        tempo.bpm = bpm;
+    }
     Ok(())
 }
 
@@ -334,53 +209,76 @@ fn ui_sys(
 fn add_instrument_channel(
     asset: &'static str,
     icon: &str,
-    default_pattern: impl Into<DrumPattern>,
+    default_pattern: DefaultPattern,
     default_mute: bool,
     parent: &mut EntityCommands,
     loader: &AssetServer,
-    filter_track: &mut TrackHandle,
 ) {
     // The parent passed in here is the drum_machine entity from the setup_sys function.
     // We are adding a child entity to the drum_machine entity for each instrument channel.
+    // The drum machine entity also holds the KiraClock that drives every channel's sequencer.
+    let clock = parent.id();
     parent.with_children(|parent| {
         let a = loader.load(asset);
-        let mut channel = parent.spawn(KiraStaticSoundHandle(a));
+        let mut channel = parent.spawn(KiraStaticSoundHandle(a.clone()));
         let name = asset.split('.').next().unwrap();
 
         // This ChannelInfo component is defined specifically for this demo. It is used to hold the
         // channel state to show in the UI and to hold the volume level and mute status of the
-        // channel which will be applied every frame by the apply_levels_sys system.
-        channel.insert(ChannelInfo {
+        // channel which are bound to the effects below.
+        let info = ChannelInfo {
             name: name.to_string(),
             icon: icon.to_string(),
             muted: default_mute,
             ..Default::default()
-        });
-
-        // Next we add a track to the channel and adding a reverb effect to the track. Both of these
-        // steps are optional. If you don't specify a track when playing a sound it will play on
-        // a default Main track.
-        let reverb = ReverbBuilder::new().mix(0.0).stereo_width(0.0);
-        let volume = if default_mute {
-            Decibels::SILENCE
-        } else {
-            Decibels::IDENTITY
         };
-        let mut track = TrackBuilder::new().volume(volume);
-        let reverb_handle = track.add_effect(reverb);
-        channel.insert(TrackReverb(reverb_handle));
-
-        // The reverb handle is returned directly from the track builder even before we've sent it
-        // to Kira so it's our responsibility to hold onto it in a component if we want to be able
-        // to modify it later.
 
-        // We send the track builder to Kira along with the entity id for this channel. Once added
-        // the KiraPlugin will add the track to KiraTracks component on the channel entity.
-        let track_handle = filter_track.add_sub_track(track).unwrap();
-        channel.insert(KiraTrackHandle(track_handle));
+        // Next we add a reverb and a volume control to the channel. Because the channel has effect
+        // components the KiraPlugin creates a track holding these effects for it, as a sub-track of
+        // the drum machine's filter track. The track handle will be added in a KiraTrackHandle
+        // component on the channel entity. Both of these steps are optional. If you don't specify
+        // a track when playing a sound it will play on a default Main track.
+        channel.insert((
+            KiraReverb {
+                mix: Mix::DRY,
+                stereo_width: 0.0,
+                ..default()
+            },
+            KiraVolumeControl(Decibels::SILENCE),
+        ));
+        channel.insert(info);
+
+        // The volume and reverb sliders of the UI only change the ChannelInfo component. These
+        // bindings read it every frame and tween the effect parameters to match, the volume
+        // mapping the slider's 0..1 to -60..0dB. Each binding is an entity of its own, spawned as
+        // a child of the channel so it goes away with it.
+        let channel_id = channel.id();
+        channel.with_children(|bindings| {
+            bindings.spawn(KiraParameterBinding::from_component(
+                channel_id,
+                |info: &ChannelInfo| if info.muted { 0.0 } else { info.volume },
+                channel_id,
+                KiraModulatedParameter::VolumeControl,
+                KiraParameterCurve::linear((0.0, 1.0), (Decibels::SILENCE.0 as f64, 0.0)),
+            ));
+            bindings.spawn(KiraParameterBinding::from_component(
+                channel_id,
+                |info: &ChannelInfo| info.reverb,
+                channel_id,
+                KiraModulatedParameter::ReverbMix,
+                KiraParameterCurve::linear((0.0, 1.0), (0.0, 1.0)),
+            ));
+        });
 
-        // Finally we insert the default pattern for this channel.
-        channel.insert(default_pattern.into());
+        // Finally we insert a sequencer holding the default pattern for this channel. The
+        // KiraPlugin schedules the active steps on the clock ahead of time so every step is played
+        // precisely on its tick, regardless of the frame rate.
+        let sequencer = KiraSequencer::new(clock, STEPS).with_track(KiraSequencerTrack::new(
+            a,
+            Some(channel.id()),
+            default_pattern.steps(),
+        ));
+        channel.insert(sequencer);
     });
 }
 
@@ -394,11 +292,11 @@ fn container_size_for_cells(sizes: &[f32], padding: f32) -> f32 {
 
 fn machine_ui(
     ui: &mut egui::Ui,
-    bpm: &mut Bpm,
-    filter: &mut MainFilter,
+    bpm: &mut f64,
+    filter: &mut KiraFilter,
     // Used to draw the channels in the correct order.
     channel_ids: Query<&Children, With<DrumMachine>>,
-    mut channels: Query<(Entity, &mut DrumPattern)>,
+    mut channels: Query<(Entity, &mut KiraSequencer)>,
     mut chan_mute: Query<&mut ChannelInfo>,
 ) -> Result<(), BevyError> {
     let padding_x = ui.spacing().item_spacing.x;
@@ -427,12 +325,12 @@ fn machine_ui(
                             let paint_rect = paint_rect.shrink(-MACHINE_H_PADDING);
                             ui.painter().rect_filled(paint_rect, 8.0, bg_color);
                             ui.add(
-                                egui::Slider::new(&mut bpm.0, 20.0..=220.0)
+                                egui::Slider::new(bpm, 20.0..=220.0)
                                     .text("BPM")
                                     .clamping(egui::SliderClamping::Always),
                             );
                             ui.add(
-                                egui::Slider::new(&mut filter.1, 0.0..=1.0)
+                                egui::Slider::new(&mut filter.mix.0, 0.0..=1.0)
                                     .text("Filter")
                                     .clamping(egui::SliderClamping::Always),
                             );
@@ -441,9 +339,9 @@ fn machine_ui(
                             let mut in_order = channels.iter_many_mut(chan_ids);
 
                             let mut chan_number = 0;
-                            while let Some((chan_id, mut pattern)) = in_order.fetch_next() {
+                            while let Some((chan_id, mut sequencer)) = in_order.fetch_next() {
                                 let mut chan_mut = chan_mute.get_mut(chan_id).unwrap();
-                                channel_view(ui, chan_number, &mut chan_mut, &mut pattern);
+                                channel_view(ui, chan_number, &mut chan_mut, &mut sequencer);
                                 chan_number += 1;
                             }
                             control_legend_view(ui);
@@ -460,7 +358,7 @@ fn channel_view(
     ui: &mut egui::Ui,
     channel_number: u32,
     info: &mut ChannelInfo,
-    drum_pattern: &mut DrumPattern,
+    sequencer: &mut KiraSequencer,
 ) {
     StripBuilder::new(ui)
         .size(Size::exact(CHANNEL_ROW_HEIGHT))
@@ -494,7 +392,8 @@ fn channel_view(
                             is_muted,
                         );
                     });
-                    let steps = &mut drum_pattern.steps[..];
+                    // Every channel has its own sequencer with a single track.
+                    let steps = &mut sequencer.tracks[0].steps[..];
                     for beat in 0..4 {
                         strip.cell(|ui| {
                             let mut beat_color = base_color;
@@ -638,7 +537,7 @@ fn beat_view(
     on_color: Color32,
     off_color: Color32,
     beat: usize,
-    steps: &mut [bool],
+    steps: &mut [KiraStep],
 ) {
     ui.columns(4, |columns| {
         for (i, ui) in columns.iter_mut().enumerate() {
@@ -647,10 +546,10 @@ fn beat_view(
             ui.painter().rect_filled(
                 ui.available_rect_before_wrap().shrink(1.0),
                 4.0,
-                if steps[i] { on_color } else { off_color },
+                if steps[i].active { on_color } else { off_color },
             );
             if target.clicked() {
-                steps[i] = !steps[i];
+                steps[i].active = !steps[i].active;
             }
         }
     });
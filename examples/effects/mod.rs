@@ -1,3 +0,0 @@
-mod level_monitor;
-pub use level_monitor::LevelMonitorBuilder;
-pub use level_monitor::LevelMonitorHandle;
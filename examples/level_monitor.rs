@@ -6,15 +6,12 @@ use bevy_egui::{
     egui::{self},
 };
 use bevy_mod_kira::{
-    KiraContext, KiraPlaySoundEvent, KiraPlugin, KiraStaticSoundAsset, KiraStaticSoundHandle,
-    KiraTrackHandle,
+    KiraContext, KiraMeterBuilder, KiraPlaySoundEvent, KiraPlugin, KiraStaticSoundAsset,
+    KiraStaticSoundHandle, KiraTrackHandle, KiraTrackLevels,
 };
 use egui_plot::{BarChart, HLine, LineStyle};
 use kira::{sound::static_sound::StaticSoundSettings, track::TrackBuilder};
 
-mod effects;
-use effects::{LevelMonitorBuilder, LevelMonitorHandle};
-const SAMPLES: usize = 16;
 mod color_utils;
 use color_utils::*;
 
@@ -55,12 +52,6 @@ impl<const N: i32> Default for TimerMs<N> {
     }
 }
 
-#[derive(Component)]
-struct LevelsHandle<const N: usize>(LevelMonitorHandle<N>);
-
-// Ringbuf is a lock free producer, so we can use it in the audio thread.
-unsafe impl<const N: usize> Sync for LevelsHandle<N> {}
-
 #[derive(Component)]
 struct Panning(f32);
 
@@ -69,15 +60,14 @@ fn setup_sys(mut commands: Commands, loader: Res<AssetServer>, mut kira: NonSend
     let a = loader.load("hit.ogg");
     let mut entity = commands.spawn(KiraStaticSoundHandle(a));
 
-    // This LevelMonitorBuilder is defined in the examples directory. We're defined this custom
-    // effect type to extract samples from the track's stream so that we can show a level meter.
-    let monitor = LevelMonitorBuilder::<SAMPLES>;
-
+    // The KiraMeterBuilder effect measures the levels of the track's stream so that we can show
+    // a level meter.
     let mut track = TrackBuilder::new();
-    let monitor_handle = track.add_effect(monitor);
+    let meter_handle = track.add_effect(KiraMeterBuilder);
 
-    // Hold onto the effect handle so that we can exract the samples from it in another system.
-    entity.insert(LevelsHandle(monitor_handle));
+    // Inserting the meter handle makes the KiraPlugin write the measured levels into
+    // a KiraTrackLevels component on the same entity every frame.
+    entity.insert(meter_handle);
     entity.insert(Panning(0.0));
     let track_handle = kira
         .add_track(track)
@@ -116,13 +106,14 @@ struct Peaks {
     right_peak: f32,
 }
 
-fn dbs_from_rms(rms: f32) -> f32 {
-    100.0 + (20.0 * rms.log10()).max(-100.0)
+// Maps -100 to 0 dB to a height of 0 to 100.
+fn bar_height(dbs: kira::Decibels) -> f32 {
+    100.0 + dbs.0.max(-100.0)
 }
 
 fn ui_sys(
     mut ctx: EguiContexts,
-    mut query: Query<(&mut LevelsHandle<SAMPLES>, &mut Panning)>,
+    mut query: Query<(&KiraTrackLevels, &mut Panning)>,
     mut peaks: Local<Peaks>,
 ) -> Result<(), BevyError> {
     let ctx = ctx.try_ctx_mut();
@@ -131,27 +122,14 @@ fn ui_sys(
         return Ok(());
     }
 
-    let (mut monitor_handle, mut panning) = query.single_mut()?;
-    // Pull a sample containing a window of SAMPLES frames from the LevelMonitor effect.
-    // See the level_monitor/mod.rs file to see how these samples are extracted.
-    if let Ok(levels) = monitor_handle.0.get_sample() {
-        let samples = levels.window.len() as f32;
-        // Do some math to determine the decible level of the left and right channels.
-        let squares = levels
-            .window
-            .iter()
-            .map(|x| (x.left * x.left, x.right * x.right))
-            .fold((0.0, 0.0), |(l, r), (nl, nr)| (l + nl, r + nr));
-        let rms = ((squares.0 / samples).sqrt(), (squares.1 / samples).sqrt());
-        // 0 - 100 represents -100 to 0 dB
-        let dbs = (dbs_from_rms(rms.0), dbs_from_rms(rms.1));
-
-        let (left, right) = dbs;
-        peaks.left = peaks.left.max(left);
-        peaks.left_peak = peaks.left_peak.max(left);
-        peaks.right = peaks.right.max(right);
-        peaks.right_peak = peaks.right_peak.max(right);
-    }
+    let (levels, mut panning) = query.single_mut()?;
+    // The levels are measured by the KiraMeterBuilder effect on the audio thread, use the RMS
+    // level of each channel for the bars.
+    let (left, right) = (bar_height(levels.rms[0]), bar_height(levels.rms[1]));
+    peaks.left = peaks.left.max(left);
+    peaks.left_peak = peaks.left_peak.max(left);
+    peaks.right = peaks.right.max(right);
+    peaks.right_peak = peaks.right_peak.max(right);
 
     // The rest is just egui code to draw the level meters.
     let fast_decay = 0.90;
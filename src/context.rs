@@ -6,7 +6,9 @@ use kira::{
     AudioManager, AudioManagerSettings,
     backend::cpal::CpalBackend,
     clock::{ClockHandle, ClockSpeed},
-    track::{TrackBuilder, TrackHandle},
+    modulator::ModulatorBuilder,
+    sound::static_sound::{StaticSoundData, StaticSoundHandle},
+    track::{SendTrackBuilder, SendTrackHandle, TrackBuilder, TrackHandle},
 };
 
 /// KiraContext is a non-send resource that provides access to an initialized `kira::AudioManager`.
@@ -56,6 +58,21 @@ impl KiraContext {
         }
     }
 
+    /// Plays a static sound and returns its kira handle directly rather than wrapped in
+    /// a [`KiraPlayingSound`].
+    pub fn play_static(
+        &mut self,
+        sound: StaticSoundData,
+        track: Option<&mut KiraTrackHandle>,
+    ) -> Result<StaticSoundHandle, Error> {
+        let manager = self.get_manager()?;
+        let handle = match track {
+            Some(track) => track.0.play(sound)?,
+            None => manager.main_track().play(sound)?,
+        };
+        Ok(handle)
+    }
+
     pub fn add_clock(&mut self, clock_speed: ClockSpeed) -> Result<ClockHandle, Error> {
         let manager = self.get_manager()?;
         manager.add_clock(clock_speed).map_err(|e| e.into())
@@ -66,6 +83,16 @@ impl KiraContext {
         manager.add_sub_track(track).map_err(|e| e.into())
     }
 
+    pub fn add_send_track(&mut self, track: SendTrackBuilder) -> Result<SendTrackHandle, Error> {
+        let manager = self.get_manager()?;
+        manager.add_send_track(track).map_err(|e| e.into())
+    }
+
+    pub fn add_modulator<B: ModulatorBuilder>(&mut self, builder: B) -> Result<B::Handle, Error> {
+        let manager = self.get_manager()?;
+        manager.add_modulator(builder).map_err(|e| e.into())
+    }
+
     pub fn get_manager(&mut self) -> Result<&mut AudioManager, Error> {
         if let Some(manager) = &mut self.manager {
             return Ok(manager);
@@ -0,0 +1,49 @@
+// Kira effects provided by the crate. Effects are added to a track when it is built with
+// `TrackBuilder::add_effect`, the returned handle is usually inserted as a component on the entity
+// holding the track's `KiraTrackHandle`.
+//
+// The meter, spectrum analyzer, onset detector and recorder pass the audio through unchanged. Their
+// handles read what the effect measured every frame and write it to a component or an event of the
+// entity they are inserted on.
+
+use bevy::prelude::*;
+
+mod builtin;
+mod custom;
+mod fft;
+mod meter;
+mod onset;
+mod recorder;
+mod shared;
+mod spectrum;
+pub use builtin::*;
+pub use custom::*;
+pub use meter::*;
+pub use onset::*;
+pub use recorder::*;
+pub use spectrum::*;
+
+pub(crate) struct KiraEffectsPlugin;
+
+impl Plugin for KiraEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<KiraOnsetDetected>()
+            .add_event::<KiraRecordingFinished>()
+            .add_systems(PreUpdate, (track_levels_sys, spectrum_sys, onset_sys))
+            .add_systems(PreUpdate, add_effect_tracks_sys)
+            .add_systems(
+                PostUpdate,
+                (
+                    recorder_sys,
+                    builtin_effect_systems::<KiraEq>(),
+                    builtin_effect_systems::<KiraFilter>(),
+                    builtin_effect_systems::<KiraCompressor>(),
+                    builtin_effect_systems::<KiraDistortion>(),
+                    builtin_effect_systems::<KiraDelay>(),
+                    builtin_effect_systems::<KiraReverb>(),
+                    builtin_effect_systems::<KiraPanningControl>(),
+                    builtin_effect_systems::<KiraVolumeControl>(),
+                ),
+            );
+    }
+}
@@ -0,0 +1,760 @@
+// Components for the effects that come with kira. When an entity without a `KiraTrackHandle` has any
+// of these components a track is created for it holding the effects, in the order of the fields of
+// `BuiltinEffects`. The track is a sub-track of the parent entity's track when it has one. Changes
+// to the components are then forwarded to the effects.
+
+use std::time::Duration;
+
+use bevy::{
+    ecs::{query::QueryData, schedule::ScheduleConfigs, system::ScheduleSystem},
+    prelude::*,
+};
+use kira::{
+    Decibels, Mix, Panning, Tween,
+    effect::{
+        compressor::{CompressorBuilder, CompressorHandle},
+        delay::{DelayBuilder, DelayHandle},
+        distortion::{DistortionBuilder, DistortionHandle, DistortionKind},
+        eq_filter::{EqFilterBuilder, EqFilterHandle, EqFilterKind},
+        filter::{FilterBuilder, FilterHandle, FilterMode},
+        panning_control::{PanningControlBuilder, PanningControlHandle},
+        reverb::{ReverbBuilder, ReverbHandle},
+        volume_control::{VolumeControlBuilder, VolumeControlHandle},
+    },
+    track::TrackBuilder,
+};
+
+use crate::{KiraContext, KiraTrackHandle, util::Removed};
+
+/// The tween used to apply changes to the built-in effect components of the entity. Without it
+/// changes use the default tween of 10ms.
+///
+/// The built-in effect components ([`KiraEq`], [`KiraFilter`], [`KiraCompressor`],
+/// [`KiraDistortion`], [`KiraDelay`], [`KiraReverb`], [`KiraPanningControl`] and
+/// [`KiraVolumeControl`]) add kira's effects to a track created for the entity, in that order. The
+/// track is created when the entity has any of them and no [`KiraTrackHandle`] yet, as a sub-track
+/// of the parent entity's track if it has one. Changes to the components are then applied to the
+/// effects with this tween.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct KiraEffectTween(pub Tween);
+
+/// A kira filter on the entity's track, see [`KiraEffectTween`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraFilter {
+    pub mode: FilterMode,
+    /// The cutoff frequency in Hz.
+    pub cutoff: f64,
+    /// The emphasis of frequencies around the cutoff, from `0.0` to `1.0`.
+    pub resonance: f64,
+    pub mix: Mix,
+}
+
+impl Default for KiraFilter {
+    fn default() -> Self {
+        Self {
+            mode: FilterMode::LowPass,
+            cutoff: 1000.0,
+            resonance: 0.0,
+            mix: Mix::WET,
+        }
+    }
+}
+
+/// A kira reverb on the entity's track, see [`KiraEffectTween`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraReverb {
+    /// How much the room reverberates, from `0.0` to `1.0`.
+    pub feedback: f64,
+    /// How quickly high frequencies disappear, from `0.0` to `1.0`.
+    pub damping: f64,
+    /// From `0.0` (mono) to `1.0` (full stereo).
+    pub stereo_width: f64,
+    pub mix: Mix,
+}
+
+impl Default for KiraReverb {
+    fn default() -> Self {
+        Self {
+            feedback: 0.9,
+            damping: 0.1,
+            stereo_width: 1.0,
+            mix: Mix(0.5),
+        }
+    }
+}
+
+/// A kira delay on the entity's track, see [`KiraEffectTween`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraDelay {
+    /// How long the audio is delayed. This is fixed once the track is created.
+    pub delay_time: Duration,
+    /// The volume of the delayed audio fed back into the delay.
+    pub feedback: Decibels,
+    pub mix: Mix,
+}
+
+impl Default for KiraDelay {
+    fn default() -> Self {
+        Self {
+            delay_time: Duration::from_millis(500),
+            feedback: Decibels(-6.0),
+            mix: Mix(0.5),
+        }
+    }
+}
+
+/// A kira distortion on the entity's track, see [`KiraEffectTween`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraDistortion {
+    pub kind: DistortionKind,
+    /// The gain applied before distorting.
+    pub drive: Decibels,
+    pub mix: Mix,
+}
+
+impl Default for KiraDistortion {
+    fn default() -> Self {
+        Self {
+            kind: DistortionKind::default(),
+            drive: Decibels::IDENTITY,
+            mix: Mix::WET,
+        }
+    }
+}
+
+/// A kira compressor on the entity's track, see [`KiraEffectTween`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraCompressor {
+    /// The level in decibels above which the volume is reduced.
+    pub threshold: f64,
+    /// How much the volume above the threshold is reduced, `4.0` turns every 4dB above the
+    /// threshold into 1dB.
+    pub ratio: f64,
+    pub attack_duration: Duration,
+    pub release_duration: Duration,
+    /// The gain applied after compressing.
+    pub makeup_gain: Decibels,
+    pub mix: Mix,
+}
+
+impl Default for KiraCompressor {
+    fn default() -> Self {
+        Self {
+            threshold: 0.0,
+            ratio: 1.0,
+            attack_duration: Duration::from_millis(10),
+            release_duration: Duration::from_millis(100),
+            makeup_gain: Decibels::IDENTITY,
+            mix: Mix::WET,
+        }
+    }
+}
+
+/// One band of a [`KiraEq`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KiraEqBand {
+    pub kind: EqFilterKind,
+    /// The center frequency of a bell or the corner frequency of a shelf in Hz.
+    pub frequency: f64,
+    pub gain: Decibels,
+    /// The width of the band, higher values are narrower.
+    pub q: f64,
+}
+
+impl KiraEqBand {
+    pub fn new(kind: EqFilterKind, frequency: f64, gain: Decibels, q: f64) -> Self {
+        Self {
+            kind,
+            frequency,
+            gain,
+            q,
+        }
+    }
+}
+
+/// A kira EQ filter per band on the entity's track, see [`KiraEffectTween`]. The number of bands is
+/// fixed once the track is created.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct KiraEq {
+    pub bands: Vec<KiraEqBand>,
+}
+
+impl Default for KiraEq {
+    /// A flat three band EQ: a low shelf, a bell and a high shelf.
+    fn default() -> Self {
+        Self {
+            bands: vec![
+                KiraEqBand::new(EqFilterKind::LowShelf, 100.0, Decibels::IDENTITY, 1.0),
+                KiraEqBand::new(EqFilterKind::Bell, 1000.0, Decibels::IDENTITY, 1.0),
+                KiraEqBand::new(EqFilterKind::HighShelf, 10_000.0, Decibels::IDENTITY, 1.0),
+            ],
+        }
+    }
+}
+
+/// A kira panning control on the entity's track, see [`KiraEffectTween`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraPanningControl(pub Panning);
+
+impl Default for KiraPanningControl {
+    fn default() -> Self {
+        Self(Panning::CENTER)
+    }
+}
+
+/// A kira volume control on the entity's track, separate from the volume of the track itself. See
+/// [`KiraEffectTween`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraVolumeControl(pub Decibels);
+
+impl Default for KiraVolumeControl {
+    fn default() -> Self {
+        Self(Decibels::IDENTITY)
+    }
+}
+
+/// Reserves a slot for built-in effects on the track created for the entity, so that their
+/// components can be added after the track is created. Without a slot an effect can only be added
+/// with the component present when the track is created.
+///
+/// An effect without its component leaves the audio unchanged: the mix is dry, the EQ gains,
+/// panning and volume are neutral. Adding the component tweens the effect in and removing it tweens
+/// it out again. Components present when the track is created always get a slot, so they can be
+/// removed and added back later. An EQ slot reserved here has three bands.
+///
+/// Slots only exist on tracks created from the effect components. A [`KiraTrackHandle`] created
+/// with [`KiraContext::add_track`] and inserted on the entity has no slots, so built-in effect
+/// components added to it are ignored with a warning. Add the effects to the `TrackBuilder`
+/// instead, or let the track be created from the components.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KiraEffectSlots {
+    pub eq: bool,
+    pub filter: bool,
+    pub compressor: bool,
+    pub distortion: bool,
+    pub delay: bool,
+    pub reverb: bool,
+    pub panning: bool,
+    pub volume: bool,
+}
+
+impl KiraEffectSlots {
+    /// Reserves a slot for every built-in effect.
+    pub fn all() -> Self {
+        Self {
+            eq: true,
+            filter: true,
+            compressor: true,
+            distortion: true,
+            delay: true,
+            reverb: true,
+            panning: true,
+            volume: true,
+        }
+    }
+}
+
+// A component describing one of kira's effects.
+pub(crate) trait BuiltinEffect: Component + Default {
+    type Handle: Send + Sync + 'static;
+
+    const NAME: &'static str;
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle;
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween);
+
+    // Makes the effect leave the audio unchanged.
+    fn bypass(handle: &mut Self::Handle, tween: Tween);
+}
+
+// The kira handle of the effect created for the `T` component of the entity.
+#[derive(Component)]
+pub(crate) struct BuiltinEffectHandle<T: BuiltinEffect>(pub(crate) T::Handle);
+
+// The systems applying changes of the built-in effect components to the effects.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct BuiltinEffectSync;
+
+// Marks an entity whose track was created for its built-in effects.
+#[derive(Component)]
+pub(crate) struct BuiltinEffectTrack;
+
+impl BuiltinEffect for KiraFilter {
+    type Handle = FilterHandle;
+
+    const NAME: &'static str = "KiraFilter";
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle {
+        track.add_effect(
+            FilterBuilder::new()
+                .mode(self.mode)
+                .cutoff(self.cutoff)
+                .resonance(self.resonance)
+                .mix(self.mix),
+        )
+    }
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween) {
+        handle.set_mode(self.mode);
+        handle.set_cutoff(self.cutoff, tween);
+        handle.set_resonance(self.resonance, tween);
+        handle.set_mix(self.mix, tween);
+    }
+
+    fn bypass(handle: &mut Self::Handle, tween: Tween) {
+        handle.set_mix(Mix::DRY, tween);
+    }
+}
+
+impl BuiltinEffect for KiraReverb {
+    type Handle = ReverbHandle;
+
+    const NAME: &'static str = "KiraReverb";
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle {
+        track.add_effect(
+            ReverbBuilder::new()
+                .feedback(self.feedback)
+                .damping(self.damping)
+                .stereo_width(self.stereo_width)
+                .mix(self.mix),
+        )
+    }
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween) {
+        handle.set_feedback(self.feedback, tween);
+        handle.set_damping(self.damping, tween);
+        handle.set_stereo_width(self.stereo_width, tween);
+        handle.set_mix(self.mix, tween);
+    }
+
+    fn bypass(handle: &mut Self::Handle, tween: Tween) {
+        handle.set_mix(Mix::DRY, tween);
+    }
+}
+
+impl BuiltinEffect for KiraDelay {
+    type Handle = DelayHandle;
+
+    const NAME: &'static str = "KiraDelay";
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle {
+        track.add_effect(
+            DelayBuilder::new()
+                .delay_time(self.delay_time)
+                .feedback(self.feedback)
+                .mix(self.mix),
+        )
+    }
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween) {
+        handle.set_feedback(self.feedback, tween);
+        handle.set_mix(self.mix, tween);
+    }
+
+    fn bypass(handle: &mut Self::Handle, tween: Tween) {
+        handle.set_mix(Mix::DRY, tween);
+    }
+}
+
+impl BuiltinEffect for KiraDistortion {
+    type Handle = DistortionHandle;
+
+    const NAME: &'static str = "KiraDistortion";
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle {
+        track.add_effect(
+            DistortionBuilder::new()
+                .kind(self.kind)
+                .drive(self.drive)
+                .mix(self.mix),
+        )
+    }
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween) {
+        handle.set_kind(self.kind);
+        handle.set_drive(self.drive, tween);
+        handle.set_mix(self.mix, tween);
+    }
+
+    fn bypass(handle: &mut Self::Handle, tween: Tween) {
+        handle.set_mix(Mix::DRY, tween);
+    }
+}
+
+impl BuiltinEffect for KiraCompressor {
+    type Handle = CompressorHandle;
+
+    const NAME: &'static str = "KiraCompressor";
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle {
+        track.add_effect(
+            CompressorBuilder::new()
+                .threshold(self.threshold)
+                .ratio(self.ratio)
+                .attack_duration(self.attack_duration)
+                .release_duration(self.release_duration)
+                .makeup_gain(self.makeup_gain)
+                .mix(self.mix),
+        )
+    }
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween) {
+        handle.set_threshold(self.threshold, tween);
+        handle.set_ratio(self.ratio, tween);
+        handle.set_attack_duration(self.attack_duration, tween);
+        handle.set_release_duration(self.release_duration, tween);
+        handle.set_makeup_gain(self.makeup_gain, tween);
+        handle.set_mix(self.mix, tween);
+    }
+
+    fn bypass(handle: &mut Self::Handle, tween: Tween) {
+        handle.set_mix(Mix::DRY, tween);
+    }
+}
+
+impl BuiltinEffect for KiraEq {
+    type Handle = Vec<EqFilterHandle>;
+
+    const NAME: &'static str = "KiraEq";
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle {
+        self.bands
+            .iter()
+            .map(|band| {
+                track.add_effect(EqFilterBuilder::new(
+                    band.kind,
+                    band.frequency,
+                    band.gain,
+                    band.q,
+                ))
+            })
+            .collect()
+    }
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween) {
+        if self.bands.len() != handle.len() {
+            warn!(
+                "KiraEq has {} bands but its track was created with {}, the extra bands are ignored",
+                self.bands.len(),
+                handle.len()
+            );
+        }
+        for (band, handle) in self.bands.iter().zip(handle.iter_mut()) {
+            handle.set_kind(band.kind);
+            handle.set_frequency(band.frequency, tween);
+            handle.set_gain(band.gain, tween);
+            handle.set_q(band.q, tween);
+        }
+    }
+
+    fn bypass(handle: &mut Self::Handle, tween: Tween) {
+        for handle in handle.iter_mut() {
+            handle.set_gain(Decibels::IDENTITY, tween);
+        }
+    }
+}
+
+impl BuiltinEffect for KiraPanningControl {
+    type Handle = PanningControlHandle;
+
+    const NAME: &'static str = "KiraPanningControl";
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle {
+        track.add_effect(PanningControlBuilder(self.0.into()))
+    }
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween) {
+        handle.set_panning(self.0, tween);
+    }
+
+    fn bypass(handle: &mut Self::Handle, tween: Tween) {
+        handle.set_panning(Panning::CENTER, tween);
+    }
+}
+
+impl BuiltinEffect for KiraVolumeControl {
+    type Handle = VolumeControlHandle;
+
+    const NAME: &'static str = "KiraVolumeControl";
+
+    fn add_to(&self, track: &mut TrackBuilder) -> Self::Handle {
+        track.add_effect(VolumeControlBuilder::new(self.0))
+    }
+
+    fn apply(&self, handle: &mut Self::Handle, tween: Tween) {
+        handle.set_volume(self.0, tween);
+    }
+
+    fn bypass(handle: &mut Self::Handle, tween: Tween) {
+        handle.set_volume(Decibels::IDENTITY, tween);
+    }
+}
+
+#[derive(QueryData)]
+pub(crate) struct BuiltinEffects {
+    eq: Option<&'static KiraEq>,
+    filter: Option<&'static KiraFilter>,
+    compressor: Option<&'static KiraCompressor>,
+    distortion: Option<&'static KiraDistortion>,
+    delay: Option<&'static KiraDelay>,
+    reverb: Option<&'static KiraReverb>,
+    panning: Option<&'static KiraPanningControl>,
+    volume: Option<&'static KiraVolumeControl>,
+    slots: Option<&'static KiraEffectSlots>,
+}
+
+type BuiltinEffectHandles = (
+    BuiltinEffectHandle<KiraEq>,
+    BuiltinEffectHandle<KiraFilter>,
+    BuiltinEffectHandle<KiraCompressor>,
+    BuiltinEffectHandle<KiraDistortion>,
+    BuiltinEffectHandle<KiraDelay>,
+    BuiltinEffectHandle<KiraReverb>,
+    BuiltinEffectHandle<KiraPanningControl>,
+    BuiltinEffectHandle<KiraVolumeControl>,
+);
+
+type WithBuiltinEffect = Or<(
+    With<KiraEq>,
+    With<KiraFilter>,
+    With<KiraCompressor>,
+    With<KiraDistortion>,
+    With<KiraDelay>,
+    With<KiraReverb>,
+    With<KiraPanningControl>,
+    With<KiraVolumeControl>,
+    With<KiraEffectSlots>,
+)>;
+
+// Entities with built-in effects that need a track.
+type NewEffectTrack = (
+    WithBuiltinEffect,
+    Without<KiraTrackHandle>,
+    Without<BuiltinEffectTrack>,
+);
+
+fn add_effect<T: BuiltinEffect>(
+    effect: Option<&T>,
+    reserved: bool,
+    track: &mut TrackBuilder,
+    entity: &mut EntityCommands,
+) {
+    let handle = match effect {
+        Some(effect) => effect.add_to(track),
+        None if reserved => {
+            let mut handle = T::default().add_to(track);
+            let instant = Tween {
+                duration: Duration::ZERO,
+                ..default()
+            };
+            T::bypass(&mut handle, instant);
+            handle
+        }
+        None => return,
+    };
+    entity.insert(BuiltinEffectHandle::<T>(handle));
+}
+
+pub(super) fn add_effect_tracks_sys(
+    mut commands: Commands,
+    mut kira: NonSendMut<KiraContext>,
+    query: Query<(Entity, BuiltinEffects, Option<&ChildOf>), NewEffectTrack>,
+    mut tracks: Query<&mut KiraTrackHandle>,
+) {
+    for (eid, effects, child_of) in query.iter() {
+        let parent = child_of.map(ChildOf::parent);
+        if let Some(parent) = parent {
+            // Wait for the track of the parent to be created first.
+            if query.contains(parent) {
+                continue;
+            }
+        }
+        let mut track = TrackBuilder::new();
+        let mut entity = commands.entity(eid);
+        // Only try once, even if the track can't be added.
+        entity.insert(BuiltinEffectTrack);
+        let slots = effects.slots.copied().unwrap_or_default();
+        add_effect(effects.eq, slots.eq, &mut track, &mut entity);
+        add_effect(effects.filter, slots.filter, &mut track, &mut entity);
+        add_effect(
+            effects.compressor,
+            slots.compressor,
+            &mut track,
+            &mut entity,
+        );
+        add_effect(
+            effects.distortion,
+            slots.distortion,
+            &mut track,
+            &mut entity,
+        );
+        add_effect(effects.delay, slots.delay, &mut track, &mut entity);
+        add_effect(effects.reverb, slots.reverb, &mut track, &mut entity);
+        add_effect(effects.panning, slots.panning, &mut track, &mut entity);
+        add_effect(effects.volume, slots.volume, &mut track, &mut entity);
+        let handle = match parent.and_then(|parent| tracks.get_mut(parent).ok()) {
+            Some(mut parent) => parent.0.add_sub_track(track).map_err(|e| e.into()),
+            None => kira.add_track(track),
+        };
+        match handle {
+            Ok(handle) => {
+                entity.insert(KiraTrackHandle(handle));
+            }
+            Err(e) => {
+                error!("Error adding the effect track for {:?}: {}", eid, e);
+                entity.remove::<BuiltinEffectHandles>();
+            }
+        }
+    }
+}
+
+type SyncedEffect<T> = (
+    &'static T,
+    &'static mut BuiltinEffectHandle<T>,
+    Option<&'static KiraEffectTween>,
+);
+
+fn sync_effect_sys<T: BuiltinEffect>(mut query: Query<SyncedEffect<T>, Changed<T>>) {
+    for (effect, mut handle, tween) in query.iter_mut() {
+        let tween = tween.map(|tween| tween.0).unwrap_or_default();
+        effect.apply(&mut handle.0, tween);
+    }
+}
+
+fn bypass_removed_effect_sys<T: BuiltinEffect>(
+    mut removed: Removed<T>,
+    mut query: Query<(&mut BuiltinEffectHandle<T>, Option<&KiraEffectTween>)>,
+) {
+    for eid in removed.read() {
+        let Ok((mut handle, tween)) = query.get_mut(eid) else {
+            continue;
+        };
+        let tween = tween.map(|tween| tween.0).unwrap_or_default();
+        T::bypass(&mut handle.0, tween);
+    }
+}
+
+// Effects added to a track without a slot for them.
+type MissingSlot<T> = (
+    Added<T>,
+    With<KiraTrackHandle>,
+    Without<BuiltinEffectHandle<T>>,
+);
+
+fn missing_slot_sys<T: BuiltinEffect>(query: Query<Entity, MissingSlot<T>>) {
+    for eid in query.iter() {
+        warn!(
+            "{} was added to {:?} but its track has no slot for it, reserve one with \
+            KiraEffectSlots before the track is created",
+            T::NAME,
+            eid
+        );
+    }
+}
+
+// The systems keeping the `T` effects in sync with their components.
+pub(super) fn builtin_effect_systems<T: BuiltinEffect>() -> ScheduleConfigs<ScheduleSystem> {
+    (
+        sync_effect_sys::<T>,
+        bypass_removed_effect_sys::<T>,
+        missing_slot_sys::<T>,
+    )
+        .in_set(BuiltinEffectSync)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use kira::{
+        Frame,
+        effect::{Effect, EffectBuilder},
+        info::MockInfoBuilder,
+    };
+
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    // The peak of a sine of the given frequency once it went through the effect.
+    fn peak(effect: &mut dyn Effect, frequency: f32) -> f32 {
+        let info = MockInfoBuilder::new().build();
+        effect.on_start_processing();
+        let mut frames: Vec<Frame> = (0..4096)
+            .map(|i| Frame::from_mono((TAU * frequency * i as f32 / SAMPLE_RATE as f32).sin()))
+            .collect();
+        for chunk in frames.chunks_mut(128) {
+            effect.process(chunk, 1.0 / SAMPLE_RATE as f64, &info);
+        }
+        // Skip the filters settling.
+        frames[2048..]
+            .iter()
+            .map(|frame| frame.left.abs())
+            .fold(0.0, f32::max)
+    }
+
+    // An app syncing the `T` component of `entity` to the effect with `handle`.
+    fn app<T: BuiltinEffect>(effect: T, handle: T::Handle) -> (App, Entity) {
+        let mut app = App::new();
+        app.add_systems(Update, builtin_effect_systems::<T>());
+        let instant = Tween {
+            duration: Duration::ZERO,
+            ..default()
+        };
+        let eid = app
+            .world_mut()
+            .spawn((
+                effect,
+                BuiltinEffectHandle::<T>(handle),
+                KiraEffectTween(instant),
+            ))
+            .id();
+        app.update();
+        (app, eid)
+    }
+
+    #[test]
+    fn filter_changes_reach_its_slot_and_removal_bypasses_it() {
+        let filter = KiraFilter {
+            cutoff: 20_000.0,
+            ..default()
+        };
+        let (mut effect, handle) = FilterBuilder::new().cutoff(filter.cutoff).build();
+        effect.init(SAMPLE_RATE, 128);
+        let (mut app, eid) = app(filter, handle);
+        assert!(peak(effect.as_mut(), 5000.0) > 0.9);
+        app.world_mut().get_mut::<KiraFilter>(eid).unwrap().cutoff = 200.0;
+        app.update();
+        assert!(peak(effect.as_mut(), 5000.0) < 0.1);
+        app.world_mut().entity_mut(eid).remove::<KiraFilter>();
+        app.update();
+        assert!((peak(effect.as_mut(), 5000.0) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn eq_changes_reach_its_slot_and_removal_bypasses_it() {
+        let eq = KiraEq {
+            bands: vec![KiraEqBand::new(
+                EqFilterKind::Bell,
+                1000.0,
+                Decibels::IDENTITY,
+                1.0,
+            )],
+        };
+        let band = eq.bands[0];
+        let (mut effect, handle) =
+            EqFilterBuilder::new(band.kind, band.frequency, band.gain, band.q).build();
+        effect.init(SAMPLE_RATE, 128);
+        let (mut app, eid) = app(eq, vec![handle]);
+        assert!((peak(effect.as_mut(), 1000.0) - 1.0).abs() < 0.01);
+        app.world_mut().get_mut::<KiraEq>(eid).unwrap().bands[0].gain = Decibels(12.0);
+        app.update();
+        // +12dB is about four times the amplitude.
+        assert!((peak(effect.as_mut(), 1000.0) - 3.98).abs() < 0.1);
+        app.world_mut().entity_mut(eid).remove::<KiraEq>();
+        app.update();
+        assert!((peak(effect.as_mut(), 1000.0) - 1.0).abs() < 0.01);
+    }
+}
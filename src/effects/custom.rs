@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use kira::{
+    Frame,
+    command::{CommandReader, CommandWriter, command_writer_and_reader},
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+
+/// A custom effect whose parameters are set from systems. Wrap the effect and its initial
+/// parameters in a [`KiraEffectBuilder`], add it to a track with `TrackBuilder::add_effect` and
+/// insert the returned [`KiraEffectParams`] on an entity. Parameters set on the component are sent
+/// to the audio thread without locking, the effect always processes the latest ones.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_mod_kira::{KiraEffect, KiraEffectBuilder, KiraEffectParams};
+/// # use kira::{Frame, info::Info, track::TrackBuilder};
+/// #[derive(Clone, Copy)]
+/// struct Gain(f32);
+///
+/// struct GainEffect;
+///
+/// impl KiraEffect for GainEffect {
+///     type Params = Gain;
+///
+///     fn process(&mut self, params: &Gain, input: &mut [Frame], _dt: f64, _info: &Info) {
+///         for frame in input.iter_mut() {
+///             *frame = *frame * params.0;
+///         }
+///     }
+/// }
+///
+/// let mut track = TrackBuilder::new();
+/// let gain: KiraEffectParams<GainEffect> =
+///     track.add_effect(KiraEffectBuilder::new(GainEffect, Gain(0.5)));
+///
+/// fn mute_sys(mut gains: Query<&mut KiraEffectParams<GainEffect>>) {
+///     for mut gain in gains.iter_mut() {
+///         gain.set(Gain(0.0));
+///     }
+/// }
+/// ```
+pub trait KiraEffect: Send + 'static {
+    /// The parameters sent to the effect whenever they are set on its [`KiraEffectParams`].
+    type Params: Copy + Send + Sync + 'static;
+
+    /// Called when the effect is first sent to the renderer.
+    fn init(&mut self, _sample_rate: u32, _internal_buffer_size: usize) {}
+
+    /// Called when the sample rate of the renderer changes.
+    fn on_change_sample_rate(&mut self, _sample_rate: u32) {}
+
+    /// Transforms a slice of input frames with the latest parameters, see `kira::effect::Effect`.
+    fn process(&mut self, params: &Self::Params, input: &mut [Frame], dt: f64, info: &Info);
+}
+
+/// Builds a [`KiraEffect`] with its initial parameters.
+pub struct KiraEffectBuilder<E: KiraEffect> {
+    pub effect: E,
+    pub params: E::Params,
+}
+
+impl<E: KiraEffect> KiraEffectBuilder<E> {
+    pub fn new(effect: E, params: E::Params) -> Self {
+        Self { effect, params }
+    }
+}
+
+impl<E: KiraEffect> EffectBuilder for KiraEffectBuilder<E> {
+    type Handle = KiraEffectParams<E>;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let (writer, reader) = command_writer_and_reader();
+        (
+            Box::new(EffectWithParams {
+                effect: self.effect,
+                params: self.params,
+                reader,
+            }),
+            KiraEffectParams {
+                params: self.params,
+                writer,
+            },
+        )
+    }
+}
+
+/// Sets the parameters of a [`KiraEffect`].
+#[derive(Component)]
+pub struct KiraEffectParams<E: KiraEffect> {
+    params: E::Params,
+    writer: CommandWriter<E::Params>,
+}
+
+impl<E: KiraEffect> KiraEffectParams<E> {
+    /// The parameters last set.
+    pub fn get(&self) -> &E::Params {
+        &self.params
+    }
+
+    /// Sends new parameters to the effect.
+    pub fn set(&mut self, params: E::Params) {
+        self.params = params;
+        self.writer.write(params);
+    }
+
+    /// Changes the parameters in place and sends them to the effect.
+    pub fn update(&mut self, f: impl FnOnce(&mut E::Params)) {
+        let mut params = self.params;
+        f(&mut params);
+        self.set(params);
+    }
+}
+
+struct EffectWithParams<E: KiraEffect> {
+    effect: E,
+    params: E::Params,
+    reader: CommandReader<E::Params>,
+}
+
+impl<E: KiraEffect> Effect for EffectWithParams<E> {
+    fn init(&mut self, sample_rate: u32, internal_buffer_size: usize) {
+        self.effect.init(sample_rate, internal_buffer_size);
+    }
+
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        self.effect.on_change_sample_rate(sample_rate);
+    }
+
+    fn on_start_processing(&mut self) {
+        if let Some(params) = self.reader.read() {
+            self.params = params;
+        }
+    }
+
+    fn process(&mut self, input: &mut [Frame], dt: f64, info: &Info) {
+        self.effect.process(&self.params, input, dt, info);
+    }
+}
@@ -0,0 +1,101 @@
+use std::f32::consts::PI;
+
+// An in-place radix-2 FFT. Every buffer is allocated up front so `magnitudes` can run on the audio
+// thread.
+pub(crate) struct Fft {
+    size: usize,
+    window: Vec<f32>,
+    // The normalization that makes a full scale sine wave read as an amplitude of 1.0.
+    scale: f32,
+    twiddles: Vec<(f32, f32)>,
+    bit_reversed: Vec<usize>,
+    buffer: Vec<(f32, f32)>,
+}
+
+impl Fft {
+    // `size` is rounded up to a power of two.
+    pub(crate) fn new(size: usize) -> Self {
+        let size = size.max(2).next_power_of_two();
+        // A Hann window.
+        let window: Vec<f32> = (0..size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos())
+            .collect();
+        let scale = 2.0 / window.iter().sum::<f32>();
+        let twiddles = (0..size / 2)
+            .map(|i| {
+                let angle = -2.0 * PI * i as f32 / size as f32;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        let bits = size.trailing_zeros();
+        let bit_reversed = (0..size)
+            .map(|i| i.reverse_bits() >> (usize::BITS - bits))
+            .collect();
+        Self {
+            size,
+            window,
+            scale,
+            twiddles,
+            bit_reversed,
+            buffer: vec![(0.0, 0.0); size],
+        }
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    // Windows `samples`, which must hold `size` samples with the oldest first, and writes the
+    // amplitude of the first `size / 2` frequency bins to `magnitudes`.
+    pub(crate) fn magnitudes(
+        &mut self,
+        samples: impl Iterator<Item = f32>,
+        magnitudes: &mut [f32],
+    ) {
+        for (i, sample) in samples.take(self.size).enumerate() {
+            self.buffer[self.bit_reversed[i]] = (sample * self.window[i], 0.0);
+        }
+        let mut len = 2;
+        while len <= self.size {
+            let step = self.size / len;
+            for start in (0..self.size).step_by(len) {
+                for k in 0..len / 2 {
+                    let (wr, wi) = self.twiddles[k * step];
+                    let (ar, ai) = self.buffer[start + k];
+                    let (br, bi) = self.buffer[start + k + len / 2];
+                    let (tr, ti) = (br * wr - bi * wi, br * wi + bi * wr);
+                    self.buffer[start + k] = (ar + tr, ai + ti);
+                    self.buffer[start + k + len / 2] = (ar - tr, ai - ti);
+                }
+            }
+            len *= 2;
+        }
+        for (magnitude, (re, im)) in magnitudes.iter_mut().zip(self.buffer.iter()) {
+            *magnitude = (re * re + im * im).sqrt() * self.scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_scale_sine_reads_one_in_its_bin() {
+        let mut fft = Fft::new(1000);
+        assert_eq!(fft.size(), 1024);
+        let bin = 64;
+        let samples = (0..fft.size()).map(|i| (2.0 * PI * bin as f32 * i as f32 / 1024.0).sin());
+        let mut magnitudes = vec![0.0; fft.size() / 2];
+        fft.magnitudes(samples, &mut magnitudes);
+        assert!((magnitudes[bin] - 1.0).abs() < 1e-3, "{}", magnitudes[bin]);
+        // The Hann window spreads the sine over the neighbouring bins only.
+        assert!((magnitudes[bin + 1] - 0.5).abs() < 1e-3);
+        assert!(magnitudes[bin + 3] < 1e-3);
+        assert!(
+            magnitudes[..bin - 2]
+                .iter()
+                .all(|magnitude| *magnitude < 1e-3)
+        );
+    }
+}
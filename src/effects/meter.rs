@@ -0,0 +1,286 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use bevy::prelude::*;
+use kira::{
+    Decibels, Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+
+use super::shared::AtomicF32;
+use crate::util::amplitude_to_decibels;
+
+// The time constant of the RMS average, as used by VU meters.
+const RMS_SECONDS: f64 = 0.3;
+// Short-term loudness is measured over 3 seconds in blocks of 100ms (EBU R 128).
+const LOUDNESS_BLOCK_SECONDS: f64 = 0.1;
+const LOUDNESS_BLOCKS: usize = 30;
+// The loudness reported for silence, the absolute gate of EBU R 128.
+const MIN_LUFS: f32 = -70.0;
+
+/// Measures the peak, RMS and loudness levels of a track into the [`KiraTrackLevels`] of the entity
+/// holding its [`KiraMeterHandle`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct KiraMeterBuilder;
+
+impl EffectBuilder for KiraMeterBuilder {
+    type Handle = KiraMeterHandle;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let shared = Arc::new(MeterShared::default());
+        (
+            Box::new(Meter::new(shared.clone())),
+            KiraMeterHandle { shared },
+        )
+    }
+}
+
+/// Receives the levels measured by a [`KiraMeterBuilder`] effect.
+#[derive(Component, Debug, Clone)]
+#[require(KiraTrackLevels)]
+pub struct KiraMeterHandle {
+    shared: Arc<MeterShared>,
+}
+
+impl KiraMeterHandle {
+    // Reads the levels measured since the last call.
+    fn take_levels(&self) -> KiraTrackLevels {
+        // The effect only ever raises the peak, start over for the next frame.
+        let peak = |channel: usize| amplitude_to_decibels(self.shared.peak[channel].swap(0.0));
+        let rms = |channel: usize| amplitude_to_decibels(self.shared.rms[channel].load());
+        KiraTrackLevels {
+            peak: [peak(0), peak(1)],
+            rms: [rms(0), rms(1)],
+            lufs: self.shared.lufs.load(),
+        }
+    }
+}
+
+/// The levels of a track measured by a [`KiraMeterBuilder`] effect, channel levels are stored as
+/// `[left, right]`.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraTrackLevels {
+    /// The highest sample level since the previous frame.
+    pub peak: [Decibels; 2],
+    /// The average level over the last 300ms.
+    pub rms: [Decibels; 2],
+    /// The short-term loudness (over the last 3 seconds) in LUFS, never lower than -70.
+    pub lufs: f32,
+}
+
+impl Default for KiraTrackLevels {
+    fn default() -> Self {
+        Self {
+            peak: [Decibels::SILENCE; 2],
+            rms: [Decibels::SILENCE; 2],
+            lufs: MIN_LUFS,
+        }
+    }
+}
+
+pub(super) fn track_levels_sys(mut query: Query<(&KiraMeterHandle, &mut KiraTrackLevels)>) {
+    for (meter, mut levels) in query.iter_mut() {
+        levels.set_if_neq(meter.take_levels());
+    }
+}
+
+#[derive(Debug)]
+struct MeterShared {
+    peak: [AtomicF32; 2],
+    rms: [AtomicF32; 2],
+    lufs: AtomicF32,
+}
+
+impl Default for MeterShared {
+    fn default() -> Self {
+        Self {
+            peak: default(),
+            rms: default(),
+            lufs: AtomicF32::new(MIN_LUFS),
+        }
+    }
+}
+
+// A second order IIR filter.
+#[derive(Debug, Default, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    // The previous inputs and outputs.
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+// The K-weighting filter of ITU-R BS.1770, a high shelf followed by a high pass, with the
+// coefficients computed for any sample rate.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        ..default()
+    };
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        ..default()
+    };
+    [shelf, high_pass]
+}
+
+struct Meter {
+    shared: Arc<MeterShared>,
+    sample_rate: f64,
+    mean_squares: [f64; 2],
+    filters: [[Biquad; 2]; 2],
+    // The K-weighted energy of the block being measured and the number of frames in it.
+    block_energy: f64,
+    block_frames: usize,
+    // The mean K-weighted energy of the last blocks.
+    blocks: [f64; LOUDNESS_BLOCKS],
+    next_block: usize,
+}
+
+impl Meter {
+    fn new(shared: Arc<MeterShared>) -> Self {
+        Self {
+            shared,
+            sample_rate: 48_000.0,
+            mean_squares: [0.0; 2],
+            filters: [k_weighting(48_000.0); 2],
+            block_energy: 0.0,
+            block_frames: 0,
+            blocks: [0.0; LOUDNESS_BLOCKS],
+            next_block: 0,
+        }
+    }
+
+    fn end_block(&mut self) {
+        self.blocks[self.next_block] = self.block_energy / self.block_frames as f64;
+        self.next_block = (self.next_block + 1) % LOUDNESS_BLOCKS;
+        self.block_energy = 0.0;
+        self.block_frames = 0;
+        let energy = self.blocks.iter().sum::<f64>() / LOUDNESS_BLOCKS as f64;
+        let lufs = if energy > 0.0 {
+            ((-0.691 + 10.0 * energy.log10()) as f32).max(MIN_LUFS)
+        } else {
+            MIN_LUFS
+        };
+        self.shared.lufs.store(lufs);
+    }
+}
+
+impl Effect for Meter {
+    fn init(&mut self, sample_rate: u32, _internal_buffer_size: usize) {
+        self.on_change_sample_rate(sample_rate);
+    }
+
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f64;
+        self.filters = [k_weighting(self.sample_rate); 2];
+    }
+
+    fn process(&mut self, input: &mut [Frame], dt: f64, _info: &Info) {
+        let smoothing = 1.0 - (-dt / RMS_SECONDS).exp();
+        let block_frames = (LOUDNESS_BLOCK_SECONDS * self.sample_rate) as usize;
+        let mut peak = [0.0f32; 2];
+        for frame in input.iter() {
+            let mut energy = 0.0;
+            for (channel, sample) in [frame.left, frame.right].into_iter().enumerate() {
+                peak[channel] = peak[channel].max(sample.abs());
+                let square = (sample as f64).powi(2);
+                self.mean_squares[channel] += (square - self.mean_squares[channel]) * smoothing;
+                let [shelf, high_pass] = &mut self.filters[channel];
+                energy += high_pass.process(shelf.process(sample as f64)).powi(2);
+            }
+            self.block_energy += energy;
+            self.block_frames += 1;
+            if self.block_frames >= block_frames.max(1) {
+                self.end_block();
+            }
+        }
+        for (channel, peak) in peak.into_iter().enumerate() {
+            self.shared.peak[channel].fetch_max(peak);
+            self.shared.rms[channel].store(self.mean_squares[channel].sqrt() as f32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use kira::info::MockInfoBuilder;
+
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    // Measures `seconds` of a full scale sine wave of the given frequency in the given channels.
+    fn measure(frequency: f32, seconds: f32, channels: [bool; 2]) -> KiraTrackLevels {
+        let (mut effect, handle) = KiraMeterBuilder.build();
+        effect.init(SAMPLE_RATE, 128);
+        let info = MockInfoBuilder::new().build();
+        let len = (seconds * SAMPLE_RATE as f32) as usize;
+        let mut frames: Vec<Frame> = (0..len)
+            .map(|i| {
+                let sample = (TAU * frequency * i as f32 / SAMPLE_RATE as f32).sin();
+                Frame::new(
+                    if channels[0] { sample } else { 0.0 },
+                    if channels[1] { sample } else { 0.0 },
+                )
+            })
+            .collect();
+        for chunk in frames.chunks_mut(128) {
+            effect.process(chunk, 1.0 / SAMPLE_RATE as f64, &info);
+        }
+        handle.take_levels()
+    }
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!((a - b).abs() <= tolerance, "{a} != {b}");
+    }
+
+    #[test]
+    fn full_scale_sine_peak_and_rms() {
+        let levels = measure(1000.0, 3.0, [true, true]);
+        for channel in 0..2 {
+            assert_close(levels.peak[channel].0, 0.0, 1e-3);
+            // The RMS of a sine is its peak divided by the square root of two.
+            assert_close(levels.rms[channel].0, -3.01, 0.01);
+        }
+        let levels = measure(1000.0, 3.0, [true, false]);
+        assert_eq!(levels.peak[1], Decibels::SILENCE);
+        assert_eq!(levels.rms[1], Decibels::SILENCE);
+    }
+
+    #[test]
+    fn reference_tone_loudness() {
+        // ITU-R BS.1770: a full scale 997Hz sine reads -3.01 LKFS in one channel, 0 in both.
+        assert_close(measure(997.0, 3.0, [true, false]).lufs, -3.01, 0.05);
+        assert_close(measure(997.0, 3.0, [true, true]).lufs, 0.0, 0.05);
+        assert_eq!(measure(997.0, 3.0, [false, false]).lufs, MIN_LUFS);
+    }
+}
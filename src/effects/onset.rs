@@ -0,0 +1,287 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use kira::{
+    Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+
+use super::{fft::Fft, shared::AtomicRing};
+
+const FFT_SIZE: usize = 1024;
+// The number of previous analyses averaged for the detection threshold, about 350ms at 48kHz.
+const HISTORY: usize = 32;
+// The spectral flux below which nothing is detected, so that noise in quiet passages is ignored.
+const MIN_FLUX: f32 = 0.01;
+// Magnitudes are compressed with `ln(1 + COMPRESSION * magnitude)` so quiet partials count too.
+const COMPRESSION: f32 = 100.0;
+// The number of onsets that can be waiting to be read, far more than a frame ever produces.
+const ONSET_SLOTS: usize = 16;
+
+/// Detects onsets, such as drum hits or the start of notes, in a track and writes a
+/// [`KiraOnsetDetected`] event for each of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KiraOnsetDetectorBuilder {
+    /// How many times the recent average flux a peak has to reach to be an onset. Lower values
+    /// detect more onsets.
+    pub sensitivity: f32,
+    /// The shortest time in seconds between two onsets.
+    pub min_interval: f64,
+}
+
+impl Default for KiraOnsetDetectorBuilder {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.5,
+            min_interval: 0.1,
+        }
+    }
+}
+
+impl KiraOnsetDetectorBuilder {
+    pub fn sensitivity(self, sensitivity: f32) -> Self {
+        Self {
+            sensitivity,
+            ..self
+        }
+    }
+
+    pub fn min_interval(self, min_interval: f64) -> Self {
+        Self {
+            min_interval,
+            ..self
+        }
+    }
+}
+
+impl EffectBuilder for KiraOnsetDetectorBuilder {
+    type Handle = KiraOnsetDetectorHandle;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let onsets = Arc::new(AtomicRing::new(ONSET_SLOTS));
+        (
+            Box::new(OnsetDetector::new(self, onsets.clone())),
+            KiraOnsetDetectorHandle { onsets, read: 0 },
+        )
+    }
+}
+
+/// Receives the onsets found by a [`KiraOnsetDetectorBuilder`] effect.
+#[derive(Component, Debug)]
+pub struct KiraOnsetDetectorHandle {
+    // The time and strength of the last onsets.
+    onsets: Arc<AtomicRing<(f64, f32)>>,
+    // The number of onsets already turned into events.
+    read: usize,
+}
+
+/// Written when a [`KiraOnsetDetectorBuilder`] effect finds an onset.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct KiraOnsetDetected {
+    /// The entity holding the [`KiraOnsetDetectorHandle`].
+    pub track: Entity,
+    /// When the onset happened in seconds of audio since the effect was added to the track.
+    pub time: f64,
+    /// How far the spectral flux rose above the detection threshold, `1.0` being right at it.
+    pub strength: f32,
+}
+
+pub(super) fn onset_sys(
+    mut query: Query<(Entity, &mut KiraOnsetDetectorHandle)>,
+    mut events: EventWriter<KiraOnsetDetected>,
+) {
+    for (track, mut detector) in query.iter_mut() {
+        let detector = detector.as_mut();
+        // Onsets that were overwritten before they could be read are lost.
+        let (_, onsets) = detector.onsets.read(&mut detector.read);
+        events.write_batch(onsets.map(|(time, strength)| KiraOnsetDetected {
+            track,
+            time,
+            strength,
+        }));
+    }
+}
+
+// Onsets are found by peaks in the spectral flux (how much the spectrum grows from one analysis to
+// the next) that rise above the recent average.
+struct OnsetDetector {
+    onsets: Arc<AtomicRing<(f64, f32)>>,
+    sensitivity: f32,
+    min_interval: f64,
+    sample_rate: f64,
+    // The seconds of audio processed so far.
+    time: f64,
+    last_onset: Option<f64>,
+    // The last `FFT_SIZE` samples, `next_sample` is the oldest.
+    samples: Vec<f32>,
+    next_sample: usize,
+    until_analysis: usize,
+    fft: Fft,
+    magnitudes: Vec<f32>,
+    previous_magnitudes: Vec<f32>,
+    history: [f32; HISTORY],
+    next_history: usize,
+    // Peaks are picked one analysis late, once the flux is known to fall again.
+    last_flux: f32,
+    last_threshold: f32,
+    rising: bool,
+}
+
+impl OnsetDetector {
+    fn new(builder: KiraOnsetDetectorBuilder, onsets: Arc<AtomicRing<(f64, f32)>>) -> Self {
+        Self {
+            onsets,
+            sensitivity: builder.sensitivity,
+            min_interval: builder.min_interval,
+            sample_rate: 48_000.0,
+            time: 0.0,
+            last_onset: None,
+            samples: vec![0.0; FFT_SIZE],
+            next_sample: 0,
+            until_analysis: FFT_SIZE,
+            fft: Fft::new(FFT_SIZE),
+            magnitudes: vec![0.0; FFT_SIZE / 2],
+            previous_magnitudes: vec![0.0; FFT_SIZE / 2],
+            history: [0.0; HISTORY],
+            next_history: 0,
+            last_flux: 0.0,
+            last_threshold: MIN_FLUX,
+            rising: false,
+        }
+    }
+
+    fn analyze(&mut self) {
+        let (newest, oldest) = self.samples.split_at(self.next_sample);
+        self.fft
+            .magnitudes(oldest.iter().chain(newest).copied(), &mut self.magnitudes);
+        let mut flux = 0.0;
+        for (magnitude, previous) in self
+            .magnitudes
+            .iter()
+            .zip(self.previous_magnitudes.iter_mut())
+        {
+            let magnitude = (1.0 + COMPRESSION * magnitude).ln();
+            flux += (magnitude - *previous).max(0.0);
+            *previous = magnitude;
+        }
+        flux /= self.magnitudes.len() as f32;
+
+        if self.rising && flux <= self.last_flux && self.last_flux > self.last_threshold {
+            // The peak was found in the previous analysis, place it in the middle of its window.
+            let hop = (FFT_SIZE / 2) as f64 / self.sample_rate;
+            let window = FFT_SIZE as f64 / self.sample_rate;
+            let time = (self.time - hop - window / 2.0).max(0.0);
+            if self
+                .last_onset
+                .is_none_or(|last| time - last >= self.min_interval)
+            {
+                self.last_onset = Some(time);
+                self.onsets
+                    .push([(time, self.last_flux / self.last_threshold)]);
+            }
+        }
+
+        let average = self.history.iter().sum::<f32>() / HISTORY as f32;
+        self.rising = flux > self.last_flux;
+        self.last_flux = flux;
+        self.last_threshold = average * self.sensitivity + MIN_FLUX;
+        self.history[self.next_history] = flux;
+        self.next_history = (self.next_history + 1) % HISTORY;
+    }
+}
+
+impl Effect for OnsetDetector {
+    fn init(&mut self, sample_rate: u32, _internal_buffer_size: usize) {
+        self.on_change_sample_rate(sample_rate);
+    }
+
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f64;
+    }
+
+    fn process(&mut self, input: &mut [Frame], dt: f64, _info: &Info) {
+        for frame in input.iter() {
+            self.samples[self.next_sample] = (frame.left + frame.right) * 0.5;
+            self.next_sample = (self.next_sample + 1) % FFT_SIZE;
+            self.time += dt;
+            self.until_analysis -= 1;
+            if self.until_analysis == 0 {
+                self.analyze();
+                self.until_analysis = FFT_SIZE / 2;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use kira::info::MockInfoBuilder;
+
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    struct Detector {
+        effect: Box<dyn Effect>,
+        app: App,
+    }
+
+    impl Detector {
+        fn new() -> Self {
+            let (mut effect, handle) = KiraOnsetDetectorBuilder::default().build();
+            effect.init(SAMPLE_RATE, 128);
+            let mut app = App::new();
+            app.add_event::<KiraOnsetDetected>()
+                .add_systems(Update, onset_sys);
+            app.world_mut().spawn(handle);
+            Self { effect, app }
+        }
+
+        // Processes the samples and returns the onsets found in them.
+        fn process(&mut self, samples: impl Iterator<Item = f32>) -> Vec<KiraOnsetDetected> {
+            let info = MockInfoBuilder::new().build();
+            let mut frames: Vec<Frame> = samples.map(Frame::from_mono).collect();
+            for chunk in frames.chunks_mut(128) {
+                self.effect.process(chunk, 1.0 / SAMPLE_RATE as f64, &info);
+            }
+            self.app.update();
+            self.app
+                .world_mut()
+                .resource_mut::<Events<KiraOnsetDetected>>()
+                .drain()
+                .collect()
+        }
+    }
+
+    fn silence(seconds: f32) -> impl Iterator<Item = f32> {
+        std::iter::repeat_n(0.0, (seconds * SAMPLE_RATE as f32) as usize)
+    }
+
+    fn tone(start: usize, seconds: f32) -> impl Iterator<Item = f32> {
+        let len = (seconds * SAMPLE_RATE as f32) as usize;
+        (start..start + len).map(|i| 0.5 * (TAU * 440.0 * i as f32 / SAMPLE_RATE as f32).sin())
+    }
+
+    #[test]
+    fn click_after_silence_is_one_onset() {
+        let mut detector = Detector::new();
+        let click = (0..48).map(|i| 1.0 - i as f32 / 48.0);
+        let onsets = detector.process(silence(0.5).chain(click).chain(silence(0.5)));
+        assert_eq!(onsets.len(), 1);
+        // The onset is placed in the middle of the window it was found in.
+        assert!((onsets[0].time - 0.5).abs() < 0.025, "{}", onsets[0].time);
+        assert!(onsets[0].strength > 1.0);
+    }
+
+    #[test]
+    fn steady_tone_has_no_onsets() {
+        let mut detector = Detector::new();
+        // The start of the tone is an onset, once it is steady nothing else is.
+        let start = (0.25 * SAMPLE_RATE as f32) as usize;
+        assert_eq!(detector.process(tone(0, 0.25)).len(), 1);
+        assert_eq!(detector.process(tone(start, 1.0)), []);
+    }
+}
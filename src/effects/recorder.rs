@@ -0,0 +1,356 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, poll_once},
+};
+use kira::{
+    Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+    sound::static_sound::StaticSoundData,
+};
+
+use super::shared::AtomicRing;
+use crate::sound::static_sounds::{KiraStaticSoundAsset, KiraStaticSoundData};
+
+// The number of frames the effect can get ahead of the game, about 2.7 seconds at 48kHz.
+const CAPACITY: usize = 1 << 17;
+// The number of updates a stopped recording waits for the effect to hand over its last frames,
+// after which the audio thread is assumed to have stopped.
+const STOP_WAIT: u32 = 60;
+
+/// Records the audio of a track while its [`KiraRecorderHandle`] is recording.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct KiraRecorderBuilder;
+
+impl EffectBuilder for KiraRecorderBuilder {
+    type Handle = KiraRecorderHandle;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let shared = Arc::new(RecorderShared::default());
+        (
+            Box::new(Recorder {
+                shared: shared.clone(),
+            }),
+            KiraRecorderHandle {
+                shared,
+                read: 0,
+                frames: Vec::new(),
+                stop: None,
+                stop_wait: 0,
+            },
+        )
+    }
+}
+
+/// Controls a [`KiraRecorderBuilder`] effect. When a recording is stopped the recorded audio is
+/// added as a [`KiraStaticSoundAsset`] and a [`KiraRecordingFinished`] event is written, once the
+/// audio thread has handed over the last frames and the WAV file, if any, has been written.
+#[derive(Component, Debug)]
+pub struct KiraRecorderHandle {
+    shared: Arc<RecorderShared>,
+    // The number of frames already copied out of the shared buffer.
+    read: usize,
+    frames: Vec<Frame>,
+    stop: Option<RecorderStop>,
+    // The number of updates the stopped recording has waited for the effect.
+    stop_wait: u32,
+}
+
+#[derive(Debug)]
+enum RecorderStop {
+    Asset,
+    File(PathBuf),
+}
+
+impl KiraRecorderHandle {
+    /// Starts a new recording, discarding the audio of an unfinished one.
+    pub fn start(&mut self) {
+        self.frames.clear();
+        self.stop = None;
+        self.read = self.shared.frames.written();
+        self.shared.recording.store(true, Ordering::Relaxed);
+    }
+
+    /// Stops the recording.
+    pub fn stop(&mut self) {
+        self.finish(RecorderStop::Asset);
+    }
+
+    /// Stops the recording and also writes it to a 32-bit float WAV file at `path`. Recordings that
+    /// don't fit in a WAV file (4GiB) are only added as an asset and an error is logged.
+    pub fn stop_and_save(&mut self, path: impl Into<PathBuf>) {
+        self.finish(RecorderStop::File(path.into()));
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.shared.recording.load(Ordering::Relaxed)
+    }
+
+    fn finish(&mut self, stop: RecorderStop) {
+        if self.is_recording() {
+            self.shared.recording.store(false, Ordering::Relaxed);
+            self.shared.stops.fetch_add(1, Ordering::Release);
+            self.stop = Some(stop);
+            self.stop_wait = 0;
+        }
+    }
+
+    // Whether the effect has seen the last stop, so every frame it recorded has been published.
+    fn stopped(&self) -> bool {
+        self.shared.stops_seen.load(Ordering::Acquire) == self.shared.stops.load(Ordering::Relaxed)
+    }
+
+    // Copies the frames written by the effect since the last call.
+    fn drain(&mut self) {
+        let (lost, frames) = self.shared.frames.read(&mut self.read);
+        if lost > 0 {
+            warn!("KiraRecorderHandle fell behind, {} frames were lost", lost);
+        }
+        self.frames.extend(frames);
+    }
+}
+
+/// Written when a [`KiraRecorderHandle`] finishes a recording.
+#[derive(Event, Debug, Clone)]
+pub struct KiraRecordingFinished {
+    /// The entity holding the [`KiraRecorderHandle`].
+    pub recorder: Entity,
+    pub sound: Handle<KiraStaticSoundAsset>,
+}
+
+// A recording being written to a WAV file, kept outside of the handle so that despawning the
+// recorder doesn't cancel the write.
+pub(super) struct RecordingSave {
+    recorder: Entity,
+    sound: Handle<KiraStaticSoundAsset>,
+    path: PathBuf,
+    task: Task<std::io::Result<()>>,
+}
+
+pub(super) fn recorder_sys(
+    mut query: Query<(Entity, &mut KiraRecorderHandle)>,
+    mut sounds: ResMut<Assets<KiraStaticSoundAsset>>,
+    mut events: EventWriter<KiraRecordingFinished>,
+    mut saves: Local<Vec<RecordingSave>>,
+) {
+    for (recorder, mut handle) in query.iter_mut() {
+        if !handle.is_recording() && handle.stop.is_none() {
+            continue;
+        }
+        // A process call that started before the stop may still be writing the last frames.
+        let stopped = handle.stopped();
+        handle.drain();
+        if handle.stop.is_none() {
+            continue;
+        }
+        if !stopped && handle.stop_wait < STOP_WAIT {
+            handle.stop_wait += 1;
+            continue;
+        }
+        let Some(stop) = handle.stop.take() else {
+            continue;
+        };
+        let sound = StaticSoundData {
+            sample_rate: handle.shared.sample_rate.load(Ordering::Relaxed),
+            frames: std::mem::take(&mut handle.frames).into(),
+            settings: default(),
+            slice: None,
+        };
+        let data = sound.clone();
+        let sound = sounds.add(KiraStaticSoundAsset {
+            sound: KiraStaticSoundData(sound),
+        });
+        match stop {
+            RecorderStop::Asset => {
+                events.write(KiraRecordingFinished { recorder, sound });
+            }
+            RecorderStop::File(path) => {
+                // Without the task pools, for example in tests, the file is written right away.
+                let Some(pool) = IoTaskPool::try_get() else {
+                    let result = write_wav(&path, &data);
+                    finish_save(&mut events, recorder, sound, &path, result);
+                    continue;
+                };
+                let task_path = path.clone();
+                let task = pool.spawn(async move { write_wav(&task_path, &data) });
+                saves.push(RecordingSave {
+                    recorder,
+                    sound,
+                    path,
+                    task,
+                });
+            }
+        }
+    }
+    saves.retain_mut(|save| {
+        let Some(result) = block_on(poll_once(&mut save.task)) else {
+            return true;
+        };
+        finish_save(
+            &mut events,
+            save.recorder,
+            save.sound.clone(),
+            &save.path,
+            result,
+        );
+        false
+    });
+}
+
+fn finish_save(
+    events: &mut EventWriter<KiraRecordingFinished>,
+    recorder: Entity,
+    sound: Handle<KiraStaticSoundAsset>,
+    path: &Path,
+    result: std::io::Result<()>,
+) {
+    if let Err(e) = result {
+        error!("Error writing the recording to {}: {}", path.display(), e);
+    }
+    events.write(KiraRecordingFinished { recorder, sound });
+}
+
+// The sizes of the RIFF chunk and of the data chunk of a WAV file with the given number of frames.
+// WAV files can't be larger than 4GiB, about 6.7 hours of stereo audio at 44.1kHz.
+fn wav_sizes(frames: usize) -> std::io::Result<(u32, u32)> {
+    let too_large = || {
+        std::io::Error::new(
+            std::io::ErrorKind::FileTooLarge,
+            format!("{frames} frames don't fit in a WAV file"),
+        )
+    };
+    let data_size = frames
+        .checked_mul(8)
+        .and_then(|size| u32::try_from(size).ok())
+        .ok_or_else(too_large)?;
+    let riff_size = data_size.checked_add(50).ok_or_else(too_large)?;
+    Ok((riff_size, data_size))
+}
+
+fn write_wav(path: &Path, sound: &StaticSoundData) -> std::io::Result<()> {
+    let (riff_size, data_size) = wav_sizes(sound.frames.len())?;
+    let mut bytes = Vec::with_capacity(58 + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&riff_size.to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    // The format: IEEE float, 2 channels, 4 bytes per sample.
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&18u32.to_le_bytes());
+    bytes.extend_from_slice(&3u16.to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&sound.sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sound.sample_rate * 8).to_le_bytes());
+    bytes.extend_from_slice(&8u16.to_le_bytes());
+    bytes.extend_from_slice(&32u16.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    // Formats other than PCM need the number of frames in a fact chunk.
+    bytes.extend_from_slice(b"fact");
+    bytes.extend_from_slice(&4u32.to_le_bytes());
+    bytes.extend_from_slice(&(data_size / 8).to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for frame in sound.frames.iter() {
+        bytes.extend_from_slice(&frame.left.to_le_bytes());
+        bytes.extend_from_slice(&frame.right.to_le_bytes());
+    }
+    std::fs::File::create(path)?.write_all(&bytes)
+}
+
+// The effect pushes the frames while recording. Every stop raises `stops`, which the effect copies
+// to `stops_seen` at the end of its next process call.
+#[derive(Debug)]
+struct RecorderShared {
+    recording: AtomicBool,
+    sample_rate: AtomicU32,
+    stops: AtomicU32,
+    stops_seen: AtomicU32,
+    frames: AtomicRing<Frame>,
+}
+
+impl Default for RecorderShared {
+    fn default() -> Self {
+        Self {
+            recording: AtomicBool::new(false),
+            sample_rate: AtomicU32::new(48_000),
+            stops: AtomicU32::new(0),
+            stops_seen: AtomicU32::new(0),
+            frames: AtomicRing::new(CAPACITY),
+        }
+    }
+}
+
+struct Recorder {
+    shared: Arc<RecorderShared>,
+}
+
+impl Effect for Recorder {
+    fn init(&mut self, sample_rate: u32, _internal_buffer_size: usize) {
+        self.on_change_sample_rate(sample_rate);
+    }
+
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        self.shared
+            .sample_rate
+            .store(sample_rate, Ordering::Relaxed);
+    }
+
+    fn process(&mut self, input: &mut [Frame], _dt: f64, _info: &Info) {
+        // Read before `recording` so a stop seen here also means the recording is seen as stopped.
+        let stops = self.shared.stops.load(Ordering::Acquire);
+        if self.shared.recording.load(Ordering::Relaxed) {
+            self.shared.frames.push(input.iter().copied());
+        }
+        self.shared.stops_seen.store(stops, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_header_sizes_match_the_file() {
+        let sound = StaticSoundData {
+            sample_rate: 44_100,
+            frames: Arc::new([Frame::ZERO; 3]),
+            settings: default(),
+            slice: None,
+        };
+        let path = std::env::temp_dir().join(format!("kira_recorder_{}.wav", std::process::id()));
+        write_wav(&path, &sound).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(bytes.len(), 58 + 3 * 8);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[12..16], b"fmt ");
+        assert_eq!(u32_at(16), 18);
+        assert_eq!(u32_at(24), 44_100);
+        assert_eq!(&bytes[38..42], b"fact");
+        assert_eq!(u32_at(46), 3);
+        assert_eq!(&bytes[50..54], b"data");
+        assert_eq!(u32_at(54) as usize, bytes.len() - 58);
+    }
+
+    #[test]
+    fn wav_larger_than_4gib_is_an_error() {
+        let max_frames = (u32::MAX as usize - 50) / 8;
+        assert_eq!(
+            wav_sizes(max_frames).unwrap(),
+            (max_frames as u32 * 8 + 50, max_frames as u32 * 8)
+        );
+        let error = wav_sizes(max_frames + 1).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::FileTooLarge);
+    }
+}
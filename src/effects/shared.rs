@@ -0,0 +1,171 @@
+// Lock-free values shared between the analysis effects on the audio thread and their handles.
+// Floats are stored as their bits.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+};
+
+use kira::Frame;
+
+#[derive(Debug, Default)]
+pub(super) struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    pub(super) fn new(value: f32) -> Self {
+        Self(AtomicU32::new(value.to_bits()))
+    }
+
+    pub(super) fn load(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub(super) fn store(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub(super) fn swap(&self, value: f32) -> f32 {
+        f32::from_bits(self.0.swap(value.to_bits(), Ordering::Relaxed))
+    }
+
+    // Non negative floats compare the same way as their bits, so this only works for those.
+    pub(super) fn fetch_max(&self, value: f32) {
+        self.0.fetch_max(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct AtomicF64(AtomicU64);
+
+// A value that can be stored in an `AtomicRing`.
+pub(super) trait AtomicValue: Copy {
+    type Atomic: Default + Send + Sync;
+
+    fn load(atomic: &Self::Atomic) -> Self;
+
+    fn store(self, atomic: &Self::Atomic);
+}
+
+impl AtomicValue for f32 {
+    type Atomic = AtomicF32;
+
+    fn load(atomic: &AtomicF32) -> Self {
+        atomic.load()
+    }
+
+    fn store(self, atomic: &AtomicF32) {
+        atomic.store(self);
+    }
+}
+
+impl AtomicValue for f64 {
+    type Atomic = AtomicF64;
+
+    fn load(atomic: &AtomicF64) -> Self {
+        f64::from_bits(atomic.0.load(Ordering::Relaxed))
+    }
+
+    fn store(self, atomic: &AtomicF64) {
+        atomic.0.store(self.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl<A: AtomicValue, B: AtomicValue> AtomicValue for (A, B) {
+    type Atomic = (A::Atomic, B::Atomic);
+
+    fn load(atomic: &Self::Atomic) -> Self {
+        (A::load(&atomic.0), B::load(&atomic.1))
+    }
+
+    fn store(self, atomic: &Self::Atomic) {
+        self.0.store(&atomic.0);
+        self.1.store(&atomic.1);
+    }
+}
+
+impl AtomicValue for Frame {
+    type Atomic = (AtomicF32, AtomicF32);
+
+    fn load(atomic: &Self::Atomic) -> Self {
+        let (left, right) = AtomicValue::load(atomic);
+        Frame { left, right }
+    }
+
+    fn store(self, atomic: &Self::Atomic) {
+        (self.left, self.right).store(atomic);
+    }
+}
+
+// A queue of the last values pushed by the effect. Every value is stored before it is published by
+// raising `written`, values that are overwritten before the handle reads them are lost.
+pub(super) struct AtomicRing<T: AtomicValue> {
+    written: AtomicUsize,
+    slots: Box<[T::Atomic]>,
+}
+
+impl<T: AtomicValue> AtomicRing<T> {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            written: AtomicUsize::new(0),
+            slots: (0..capacity).map(|_| T::Atomic::default()).collect(),
+        }
+    }
+
+    // The number of values pushed so far.
+    pub(super) fn written(&self) -> usize {
+        self.written.load(Ordering::Acquire)
+    }
+
+    // Only the effect pushes, so `written` can't change while the values are stored.
+    pub(super) fn push(&self, values: impl IntoIterator<Item = T>) {
+        let mut written = self.written.load(Ordering::Relaxed);
+        for value in values {
+            value.store(&self.slots[written % self.slots.len()]);
+            written += 1;
+        }
+        self.written.store(written, Ordering::Release);
+    }
+
+    // Reads the values pushed since `read` values had been read and moves `read` past them.
+    // Returns the number of values that were lost as well.
+    pub(super) fn read(&self, read: &mut usize) -> (usize, impl Iterator<Item = T> + '_) {
+        let written = self.written();
+        let first = (*read).max(written.saturating_sub(self.slots.len()));
+        let lost = first - *read;
+        *read = written;
+        let values = (first..written).map(|index| T::load(&self.slots[index % self.slots.len()]));
+        (lost, values)
+    }
+}
+
+impl<T: AtomicValue> fmt::Debug for AtomicRing<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicRing")
+            .field("written", &self.written)
+            .field("capacity", &self.slots.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_reads_new_values_and_counts_lost_ones() {
+        let ring = AtomicRing::<(f64, f32)>::new(4);
+        let mut read = 0;
+        ring.push([(1.0, 0.5), (2.0, 1.5)]);
+        let (lost, values) = ring.read(&mut read);
+        assert_eq!(lost, 0);
+        assert_eq!(values.collect::<Vec<_>>(), [(1.0, 0.5), (2.0, 1.5)]);
+        ring.push((3..9).map(|time| (time as f64, 1.0)));
+        let (lost, values) = ring.read(&mut read);
+        assert_eq!(lost, 2);
+        assert_eq!(
+            values.map(|(time, _)| time).collect::<Vec<_>>(),
+            [5.0, 6.0, 7.0, 8.0]
+        );
+        assert_eq!(read, 8);
+    }
+}
@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use kira::{
+    Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+
+use super::{fft::Fft, shared::AtomicF32};
+
+/// How the frequency range of a [`KiraSpectrumAnalyzerBuilder`] is split into bands.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KiraBandLayout {
+    /// Bands of equal width in Hz.
+    Linear,
+    /// Bands of equal width on a logarithmic scale, which matches how pitch is perceived.
+    #[default]
+    Logarithmic,
+    /// One band per octave, centered on the standard octave frequencies (..., 500Hz, 1kHz, 2kHz,
+    /// ...) that fall within the range. The number of bands follows from the range.
+    Octave,
+}
+
+/// Measures the magnitude of frequency bands of a track, for visualizers, into the [`KiraSpectrum`]
+/// of the entity holding its [`KiraSpectrumAnalyzerHandle`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KiraSpectrumAnalyzerBuilder {
+    /// The number of frames analyzed at once, rounded up to a power of two. Larger sizes separate
+    /// low frequencies better but react slower.
+    pub fft_size: usize,
+    /// The number of bands, ignored by [`KiraBandLayout::Octave`].
+    pub bands: usize,
+    pub layout: KiraBandLayout,
+    /// The lowest frequency analyzed in Hz.
+    pub min_frequency: f32,
+    /// The highest frequency analyzed in Hz.
+    pub max_frequency: f32,
+    /// How much of the previous magnitude of a band is kept with every analysis, from `0.0` (none)
+    /// to `1.0`, to smooth the bands out.
+    pub smoothing: f32,
+}
+
+impl Default for KiraSpectrumAnalyzerBuilder {
+    fn default() -> Self {
+        Self {
+            fft_size: 2048,
+            bands: 32,
+            layout: KiraBandLayout::default(),
+            min_frequency: 20.0,
+            max_frequency: 20_000.0,
+            smoothing: 0.5,
+        }
+    }
+}
+
+impl KiraSpectrumAnalyzerBuilder {
+    pub fn new(bands: usize, layout: KiraBandLayout) -> Self {
+        Self {
+            bands,
+            layout,
+            ..default()
+        }
+    }
+
+    pub fn fft_size(self, fft_size: usize) -> Self {
+        Self { fft_size, ..self }
+    }
+
+    pub fn frequency_range(self, min_frequency: f32, max_frequency: f32) -> Self {
+        Self {
+            min_frequency,
+            max_frequency,
+            ..self
+        }
+    }
+
+    pub fn smoothing(self, smoothing: f32) -> Self {
+        Self { smoothing, ..self }
+    }
+
+    // The lower and upper frequency of every band.
+    fn band_edges(&self) -> Vec<(f32, f32)> {
+        let (min, max) = (self.min_frequency.max(1.0), self.max_frequency);
+        let bands = self.bands.max(1);
+        match self.layout {
+            KiraBandLayout::Linear => {
+                let width = (max - min) / bands as f32;
+                (0..bands)
+                    .map(|i| (min + width * i as f32, min + width * (i + 1) as f32))
+                    .collect()
+            }
+            KiraBandLayout::Logarithmic => {
+                let ratio = (max / min).powf(1.0 / bands as f32);
+                (0..bands)
+                    .map(|i| (min * ratio.powi(i as i32), min * ratio.powi(i as i32 + 1)))
+                    .collect()
+            }
+            KiraBandLayout::Octave => {
+                let half_octave = 2f32.sqrt();
+                (-10..=10)
+                    .map(|i| 1000.0 * 2f32.powi(i))
+                    .filter(|center| *center >= min && *center <= max)
+                    .map(|center| (center / half_octave, center * half_octave))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl EffectBuilder for KiraSpectrumAnalyzerBuilder {
+    type Handle = KiraSpectrumAnalyzerHandle;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let edges = self.band_edges();
+        let bands: Arc<[AtomicF32]> = edges.iter().map(|_| AtomicF32::default()).collect();
+        let fft = Fft::new(self.fft_size);
+        let size = fft.size();
+        (
+            Box::new(SpectrumAnalyzer {
+                bands: bands.clone(),
+                smoothing: self.smoothing.clamp(0.0, 1.0),
+                bins: vec![(0, 1); edges.len()],
+                edges: edges.clone(),
+                samples: vec![0.0; size],
+                next_sample: 0,
+                until_analysis: size,
+                magnitudes: vec![0.0; size / 2],
+                fft,
+            }),
+            KiraSpectrumAnalyzerHandle { bands, edges },
+        )
+    }
+}
+
+/// Receives the band magnitudes measured by a [`KiraSpectrumAnalyzerBuilder`] effect.
+#[derive(Component, Debug, Clone)]
+#[require(KiraSpectrum)]
+pub struct KiraSpectrumAnalyzerHandle {
+    bands: Arc<[AtomicF32]>,
+    edges: Vec<(f32, f32)>,
+}
+
+/// The spectrum of a track measured by a [`KiraSpectrumAnalyzerBuilder`] effect.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct KiraSpectrum {
+    /// The magnitude of every band as a linear amplitude, a full scale sine wave reads as `1.0`.
+    pub bands: Vec<f32>,
+    /// The lower and upper frequency of every band in Hz.
+    pub frequencies: Vec<(f32, f32)>,
+}
+
+pub(super) fn spectrum_sys(mut query: Query<(&KiraSpectrumAnalyzerHandle, &mut KiraSpectrum)>) {
+    for (analyzer, mut spectrum) in query.iter_mut() {
+        if spectrum.frequencies != analyzer.edges {
+            spectrum.frequencies = analyzer.edges.clone();
+        }
+        let bands = analyzer.bands.iter().map(AtomicF32::load);
+        if !spectrum.bands.iter().copied().eq(bands.clone()) {
+            spectrum.bands = bands.collect();
+        }
+    }
+}
+
+// The left and right channels are mixed together and analyzed with a Hann windowed FFT of
+// `fft_size` frames, half overlapping the previous one.
+struct SpectrumAnalyzer {
+    bands: Arc<[AtomicF32]>,
+    smoothing: f32,
+    edges: Vec<(f32, f32)>,
+    // The range of FFT bins of every band for the current sample rate.
+    bins: Vec<(usize, usize)>,
+    // The last `fft_size` samples, `next_sample` is the oldest.
+    samples: Vec<f32>,
+    next_sample: usize,
+    until_analysis: usize,
+    magnitudes: Vec<f32>,
+    fft: Fft,
+}
+
+impl SpectrumAnalyzer {
+    fn analyze(&mut self) {
+        let (newest, oldest) = self.samples.split_at(self.next_sample);
+        self.fft
+            .magnitudes(oldest.iter().chain(newest).copied(), &mut self.magnitudes);
+        for (band, &(start, end)) in self.bands.iter().zip(self.bins.iter()) {
+            let magnitude = self.magnitudes[start..end]
+                .iter()
+                .copied()
+                .fold(0.0, f32::max);
+            let smoothed = band.load() * self.smoothing + magnitude * (1.0 - self.smoothing);
+            band.store(smoothed);
+        }
+    }
+}
+
+// The range of FFT bins covering the frequencies of a band.
+fn band_bins((low, high): (f32, f32), bin_width: f32, bin_count: usize) -> (usize, usize) {
+    let start = ((low / bin_width).round() as usize).min(bin_count - 1);
+    // Narrow bands still read at least one bin.
+    let end = ((high / bin_width).round() as usize).clamp(start + 1, bin_count);
+    (start, end)
+}
+
+impl Effect for SpectrumAnalyzer {
+    fn init(&mut self, sample_rate: u32, _internal_buffer_size: usize) {
+        self.on_change_sample_rate(sample_rate);
+    }
+
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        let bin_width = sample_rate as f32 / self.fft.size() as f32;
+        let bin_count = self.magnitudes.len();
+        for (bins, &edges) in self.bins.iter_mut().zip(self.edges.iter()) {
+            *bins = band_bins(edges, bin_width, bin_count);
+        }
+    }
+
+    fn process(&mut self, input: &mut [Frame], _dt: f64, _info: &Info) {
+        for frame in input.iter() {
+            self.samples[self.next_sample] = (frame.left + frame.right) * 0.5;
+            self.next_sample = (self.next_sample + 1) % self.samples.len();
+            self.until_analysis -= 1;
+            if self.until_analysis == 0 {
+                self.analyze();
+                self.until_analysis = self.samples.len() / 2;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() <= b * 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn octave_bands_are_centered_on_the_octave_frequencies() {
+        let edges = KiraSpectrumAnalyzerBuilder::new(0, KiraBandLayout::Octave).band_edges();
+        let centers: Vec<f32> = edges
+            .iter()
+            .map(|(low, high)| (low * high).sqrt())
+            .collect();
+        let expected = [
+            31.25, 62.5, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+        ];
+        assert_eq!(centers.len(), expected.len());
+        for ((center, expected), (low, high)) in centers.iter().zip(expected).zip(edges.iter()) {
+            assert_close(*center, expected);
+            assert_close(high / low, 2.0);
+        }
+    }
+
+    #[test]
+    fn logarithmic_bands_have_the_same_ratio() {
+        let edges = KiraSpectrumAnalyzerBuilder::new(3, KiraBandLayout::Logarithmic)
+            .frequency_range(100.0, 10_000.0)
+            .band_edges();
+        let expected = [
+            (100.0, 464.1589),
+            (464.1589, 2154.435),
+            (2154.435, 10_000.0),
+        ];
+        for ((low, high), (expected_low, expected_high)) in edges.iter().zip(expected) {
+            assert_close(*low, expected_low);
+            assert_close(*high, expected_high);
+        }
+    }
+
+    #[test]
+    fn band_bins_follow_the_sample_rate() {
+        let edges = KiraSpectrumAnalyzerBuilder::new(0, KiraBandLayout::Octave)
+            .frequency_range(500.0, 20_000.0)
+            .band_edges();
+        let bins_at = |sample_rate: f32| {
+            edges
+                .iter()
+                .map(|&band| band_bins(band, sample_rate / 2048.0, 1024))
+                .collect::<Vec<_>>()
+        };
+        // The 1kHz octave spans 707Hz to 1414Hz.
+        assert_eq!(bins_at(44_100.0)[1], (33, 66));
+        assert_eq!(bins_at(48_000.0)[1], (30, 60));
+        assert_eq!(bins_at(96_000.0)[1], (15, 30));
+        // The 16kHz octave goes past the highest bin at 44.1kHz.
+        assert_eq!(bins_at(44_100.0)[5], (525, 1024));
+        // Bands narrower than a bin still read one.
+        assert_eq!(band_bins((20.0, 21.0), 23.4375, 1024), (1, 2));
+    }
+}
@@ -1,15 +1,51 @@
 mod context;
+mod effects;
 mod plugins;
 mod sound;
 mod util;
 
 pub use context::KiraContext;
+pub use effects::{
+    KiraBandLayout, KiraCompressor, KiraDelay, KiraDistortion, KiraEffect, KiraEffectBuilder,
+    KiraEffectParams, KiraEffectSlots, KiraEffectTween, KiraEq, KiraEqBand, KiraFilter,
+    KiraMeterBuilder, KiraMeterHandle, KiraOnsetDetected, KiraOnsetDetectorBuilder,
+    KiraOnsetDetectorHandle, KiraPanningControl, KiraRecorderBuilder, KiraRecorderHandle,
+    KiraRecordingFinished, KiraReverb, KiraSpectrum, KiraSpectrumAnalyzerBuilder,
+    KiraSpectrumAnalyzerHandle, KiraTrackLevels, KiraVolumeControl,
+};
 pub use plugins::{
     KiraPlugin,
+    clock::{
+        KiraBeatPosition, KiraClock, KiraClockHandle, KiraClockTick, KiraStartTime, KiraTempo,
+    },
     debug::KiraDebugPlugin,
-    events::{KiraPlaySoundEvent, KiraPlayingSounds},
+    events::{
+        KiraCooldown, KiraCooldowns, KiraPlaySoundEvent, KiraPlayingSounds, KiraVoiceLimit,
+        KiraVoiceLimits, KiraVoicePolicy,
+    },
+    mixer::{
+        KiraApplySnapshot, KiraDucking, KiraMixerParameter, KiraMixerSnapshot,
+        KiraMixerSnapshotLoader, KiraTrackSnapshot, KiraTrackVolume, KiraVolumeBus,
+        KiraVolumeSettings,
+    },
+    modulators::{
+        KiraLfo, KiraModulatedParameter, KiraModulatorBinding, KiraParameterBinding,
+        KiraParameterCurve, KiraTweener,
+    },
+    music::{
+        KiraMusicGraph, KiraMusicGraphLoader, KiraMusicGraphPlayer, KiraMusicLayer,
+        KiraMusicLayers, KiraMusicPlayer, KiraMusicSegment, KiraMusicSegmentStarted, KiraMusicSync,
+        KiraMusicTransition, KiraRepeatMode, KiraTransitionPoint,
+    },
+    pause::{KiraAudioPause, KiraPauseOnState, KiraPauseScope},
+    sequencer::{KiraSequencer, KiraSequencerTrack, KiraStep},
+    time_scale::KiraTimeScale,
 };
 pub use sound::{
-    sound_types::{DynamicSoundHandle, KiraPlayable, KiraPlayingSound, KiraTrackHandle},
+    container::{KiraContainerMode, KiraSoundContainer},
+    engine::{KiraEngineLayer, KiraEngineSoundData, KiraEngineSoundHandle},
+    sound_types::{
+        DynamicSoundHandle, KiraPlayable, KiraPlayingSound, KiraSendTrackHandle, KiraTrackHandle,
+    },
     static_sounds::{KiraStaticSoundAsset, KiraStaticSoundHandle, StaticSoundFileLoader},
 };
@@ -1,10 +1,24 @@
+pub(crate) mod clock;
 pub(crate) mod debug;
 pub(crate) mod events;
+pub(crate) mod mixer;
+pub(crate) mod modulators;
+pub(crate) mod music;
+pub(crate) mod pause;
+pub(crate) mod sequencer;
+pub(crate) mod time_scale;
 
 use bevy::{asset::AssetApp, prelude::Plugin};
 
-use crate::KiraContext;
+use crate::{KiraContext, KiraSoundContainer, effects::KiraEffectsPlugin};
+use clock::KiraClockPlugin;
 use events::*;
+use mixer::KiraMixerPlugin;
+use modulators::KiraModulatorsPlugin;
+use music::KiraMusicPlugin;
+use pause::KiraPausePlugin;
+use sequencer::KiraSequencerPlugin;
+use time_scale::KiraTimeScalePlugin;
 
 pub struct KiraPlugin;
 
@@ -13,7 +27,18 @@ impl Plugin for KiraPlugin {
         app.init_non_send_resource::<KiraContext>()
             .register_asset_loader(StaticSoundFileLoader)
             .init_asset::<KiraStaticSoundAsset>()
-            .add_plugins(KiraEventsPlugin);
+            .init_asset::<KiraSoundContainer>()
+            .add_plugins((
+                KiraEventsPlugin,
+                KiraClockPlugin,
+                KiraSequencerPlugin,
+                KiraMusicPlugin,
+                KiraMixerPlugin,
+                KiraPausePlugin,
+                KiraTimeScalePlugin,
+                KiraEffectsPlugin,
+                KiraModulatorsPlugin,
+            ));
         // .add_plugin(plugins::KiraDebugPlugin);
     }
 }
@@ -0,0 +1,297 @@
+use std::ops::RangeInclusive;
+
+use anyhow::{Error, anyhow};
+use bevy::prelude::*;
+use kira::{
+    StartTime, Tween,
+    clock::{ClockHandle, ClockSpeed, ClockTime},
+};
+
+use crate::{KiraAudioPause, KiraContext, plugins::time_scale::KiraTimeScale, util::Removed};
+
+mod tempo;
+pub use tempo::*;
+
+/// A clock managed by [`KiraPlugin`]. Spawning an entity with this component asks the plugin to
+/// create a kira clock for it, the resulting handle is inserted on the same entity as
+/// a [`KiraClockHandle`]. Changes to `speed` and `running` are forwarded to the kira clock, so this
+/// component should be treated as the source of truth for the clock's settings.
+///
+/// Every tick of the clock is reported with a [`KiraClockTick`] event. The clock is also paused
+/// while [`KiraAudioPause`] pauses it.
+///
+/// [`KiraPlugin`]: crate::KiraPlugin
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraClock {
+    /// The rate that the clock ticks at.
+    pub speed: ClockSpeed,
+    /// Whether the clock is ticking. Setting this to `false` pauses the clock without resetting its
+    /// time.
+    pub running: bool,
+}
+
+impl KiraClock {
+    /// Creates a running clock with the given speed.
+    pub fn new(speed: ClockSpeed) -> Self {
+        Self {
+            speed,
+            running: true,
+        }
+    }
+}
+
+/// Holds the kira [`ClockHandle`] for an entity with a [`KiraClock`] component. The handle can be
+/// used to read the current clock time, for example to compute a `start_time` for a sound.
+///
+/// [`ClockHandle`]: https://docs.rs/kira/latest/kira/clock/struct.ClockHandle.html
+#[derive(Component)]
+pub struct KiraClockHandle(pub ClockHandle);
+
+/// This event is written once for every tick that elapsed on a [`KiraClock`]. If several ticks
+/// elapse between two frames an event is written for each one of them, in order, so that no tick
+/// is ever skipped. After the clock is reset its ticks are reported again from tick 0.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KiraClockTick {
+    /// The entity holding the `KiraClock` that ticked.
+    pub clock: Entity,
+    /// The clock time, in whole ticks, that was reached.
+    pub ticks: u64,
+}
+
+/// Describes when a sound played with a [`KiraPlaySoundEvent`] should start, relative to the clock
+/// of an entity with a [`KiraClockHandle`] (usually managed through a [`KiraClock`]). The start
+/// time is resolved against the clock when `KiraPlugin` consumes the event.
+///
+/// [`KiraPlaySoundEvent`]: crate::KiraPlaySoundEvent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KiraStartTime {
+    /// Start on the next tick of the clock.
+    NextTick(Entity),
+    /// Start when the clock reaches the given tick. If the tick has already passed the sound will
+    /// start immediately.
+    OnTick(Entity, u64),
+    /// Start on the next tick that is a multiple of `every`, for example with a clock ticking
+    /// once per beat `every: 4` starts the sound on the next bar of a 4/4 measure.
+    Quantized { clock: Entity, every: u64 },
+}
+
+impl KiraStartTime {
+    /// The entity holding the clock that this start time is relative to.
+    pub fn clock(&self) -> Entity {
+        match *self {
+            KiraStartTime::NextTick(clock) => clock,
+            KiraStartTime::OnTick(clock, _) => clock,
+            KiraStartTime::Quantized { clock, .. } => clock,
+        }
+    }
+
+    /// Resolves this start time to a kira [`StartTime`] using the current time of `clock`.
+    ///
+    /// [`StartTime`]: https://docs.rs/kira/latest/kira/enum.StartTime.html
+    pub fn resolve(&self, clock: &KiraClockHandle) -> StartTime {
+        let ticks = self.ticks(clock.0.time().ticks);
+        ClockTime::from_ticks_u64(&clock.0, ticks).into()
+    }
+
+    // The tick this start time falls on when the clock is at `now`.
+    fn ticks(&self, now: u64) -> u64 {
+        match *self {
+            KiraStartTime::NextTick(_) => now + 1,
+            KiraStartTime::OnTick(_, ticks) => ticks,
+            KiraStartTime::Quantized { every, .. } => {
+                let every = every.max(1);
+                (now / every + 1) * every
+            }
+        }
+    }
+
+    pub(crate) fn resolve_in(&self, clocks: &Query<&KiraClockHandle>) -> Result<StartTime, Error> {
+        let clock = clocks.get(self.clock()).map_err(|_| {
+            anyhow!(
+                "entity {:?} does not have a KiraClockHandle to schedule against",
+                self.clock()
+            )
+        })?;
+        Ok(self.resolve(clock))
+    }
+}
+
+// The last tick that was reported for a clock, `None` until the clock's first tick is observed.
+#[derive(Component, Default)]
+pub(crate) struct LastClockTick(Option<u64>);
+
+// The virtual time speed last applied to a clock with a `KiraTimeScale`.
+#[derive(Component)]
+pub(crate) struct ClockTimeScale(f64);
+
+impl Default for ClockTimeScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl ClockTimeScale {
+    // Scales the speed of a clock so it ticks in virtual time.
+    fn apply(&self, speed: ClockSpeed) -> ClockSpeed {
+        match speed {
+            ClockSpeed::SecondsPerTick(seconds) => ClockSpeed::SecondsPerTick(seconds / self.0),
+            ClockSpeed::TicksPerSecond(ticks) => ClockSpeed::TicksPerSecond(ticks * self.0),
+            ClockSpeed::TicksPerMinute(ticks) => ClockSpeed::TicksPerMinute(ticks * self.0),
+        }
+    }
+}
+
+pub(crate) struct KiraClockPlugin;
+
+impl Plugin for KiraClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<KiraClockTick>().add_systems(
+            PreUpdate,
+            (
+                add_tempo_clocks_sys,
+                add_clocks_sys,
+                sync_clocks_sys,
+                sync_tempo_sys,
+                clock_ticks_sys,
+                beat_position_sys,
+                remove_clocks_sys,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn add_clocks_sys(
+    mut commands: Commands,
+    mut kira: NonSendMut<KiraContext>,
+    pause: Res<KiraAudioPause>,
+    query: Query<(Entity, &KiraClock), Without<KiraClockHandle>>,
+) {
+    for (eid, clock) in query.iter() {
+        let mut handle = match kira.add_clock(clock.speed) {
+            Ok(handle) => handle,
+            Err(e) => {
+                error!("Error adding clock for entity {:?}: {}", eid, e);
+                continue;
+            }
+        };
+        if clock.running && !pause.pauses_clock(eid) {
+            handle.start();
+        }
+        commands.entity(eid).insert((
+            KiraClockHandle(handle),
+            LastClockTick::default(),
+            ClockTimeScale::default(),
+        ));
+    }
+}
+
+type SyncedClock = (
+    Entity,
+    Ref<'static, KiraClock>,
+    &'static mut KiraClockHandle,
+    &'static mut ClockTimeScale,
+    Has<KiraTimeScale>,
+);
+
+fn sync_clocks_sys(
+    time: Res<Time<Virtual>>,
+    pause: Res<KiraAudioPause>,
+    mut query: Query<SyncedClock>,
+) {
+    for (eid, clock, mut handle, mut time_scale, scaled) in query.iter_mut() {
+        let scale = if scaled {
+            time.relative_speed_f64()
+        } else {
+            1.0
+        };
+        if !clock.is_changed() && !pause.is_changed() && time_scale.0 == scale {
+            continue;
+        }
+        time_scale.0 = scale;
+        // A clock can't tick at a speed of zero, it is paused until virtual time runs again.
+        let paused = scale <= 0.0;
+        if !paused {
+            handle
+                .0
+                .set_speed(time_scale.apply(clock.speed), Tween::default());
+        }
+        let running = clock.running && !paused && !pause.pauses_clock(eid);
+        match (running, handle.0.ticking()) {
+            (true, false) => handle.0.start(),
+            (false, true) => handle.0.pause(),
+            _ => {}
+        }
+    }
+}
+
+fn clock_ticks_sys(
+    mut query: Query<(Entity, &KiraClockHandle, &mut LastClockTick)>,
+    mut ev_tick: EventWriter<KiraClockTick>,
+) {
+    for (eid, handle, mut last_tick) in query.iter_mut() {
+        let ticks = handle.0.time().ticks;
+        let Some(elapsed) = elapsed_ticks(last_tick.0, ticks, handle.0.ticking()) else {
+            // A stopped clock starts over from tick 0 once it runs again.
+            if ticks < last_tick.0.unwrap_or(0) {
+                last_tick.0 = None;
+            }
+            continue;
+        };
+        ev_tick.write_batch(elapsed.map(|ticks| KiraClockTick { clock: eid, ticks }));
+        last_tick.0 = Some(ticks);
+    }
+}
+
+// The ticks a clock went through since the `last` one reported, now that it is at `ticks`.
+fn elapsed_ticks(last: Option<u64>, ticks: u64, ticking: bool) -> Option<RangeInclusive<u64>> {
+    match last {
+        Some(last) if ticks == last => None,
+        Some(last) if ticks > last => Some(last + 1..=ticks),
+        // The clock hasn't started yet, or was stopped (for example via `ClockHandle::stop`) which
+        // resets it.
+        _ if !ticking => None,
+        // Every tick since the clock (re)started at tick 0. A reset that happens after the clock
+        // got past the last tick reported can't be told apart from the clock moving on.
+        _ => Some(0..=ticks),
+    }
+}
+
+fn remove_clocks_sys(mut commands: Commands, mut removed: Removed<KiraClock>) {
+    for eid in removed.read() {
+        // Dropping the handle removes the clock from kira.
+        commands
+            .entity(eid)
+            .try_remove::<(KiraClockHandle, LastClockTick, ClockTimeScale)>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_times_resolve_to_future_ticks() {
+        let clock = Entity::PLACEHOLDER;
+        assert_eq!(KiraStartTime::NextTick(clock).ticks(7), 8);
+        assert_eq!(KiraStartTime::OnTick(clock, 3).ticks(7), 3);
+        let bar = KiraStartTime::Quantized { clock, every: 4 };
+        assert_eq!(bar.ticks(0), 4);
+        assert_eq!(bar.ticks(7), 8);
+        assert_eq!(bar.ticks(8), 12);
+        let every_tick = KiraStartTime::Quantized { clock, every: 0 };
+        assert_eq!(every_tick.ticks(7), 8);
+    }
+
+    #[test]
+    fn every_elapsed_tick_is_reported() {
+        assert_eq!(elapsed_ticks(None, 0, false), None);
+        assert_eq!(elapsed_ticks(None, 2, true), Some(0..=2));
+        assert_eq!(elapsed_ticks(Some(2), 2, true), None);
+        assert_eq!(elapsed_ticks(Some(2), 5, true), Some(3..=5));
+        // A clock reset while running counts again from tick 0.
+        assert_eq!(elapsed_ticks(Some(5), 1, true), Some(0..=1));
+        // A stopped clock is at tick 0 but doesn't reach it until it runs again.
+        assert_eq!(elapsed_ticks(Some(5), 0, false), None);
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use kira::{Tween, clock::ClockSpeed};
+
+use super::{ClockTimeScale, KiraClock, KiraClockHandle};
+
+/// Describes the tempo and time signature of a clock entity. The clock ticks once per
+/// subdivision of a beat, so a `KiraTempo` with 4 subdivisions at 120 bpm ticks 480 times per
+/// minute.
+///
+/// When added to an entity without a [`KiraClock`] a running clock is added for it. Changes to the
+/// tempo are applied to the clock's speed using `tween`, so tempo changes can be smoothed out over
+/// time. The current musical position of the clock is available in the [`KiraBeatPosition`]
+/// component.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+#[require(KiraBeatPosition)]
+pub struct KiraTempo {
+    /// Beats per minute.
+    pub bpm: f64,
+    /// The number of beats in a bar.
+    pub beats_per_bar: u32,
+    /// The number of clock ticks in a beat.
+    pub subdivisions: u32,
+    /// The tween used when the tempo changes.
+    pub tween: Tween,
+}
+
+impl Default for KiraTempo {
+    fn default() -> Self {
+        Self {
+            bpm: 120.0,
+            beats_per_bar: 4,
+            subdivisions: 1,
+            tween: Tween::default(),
+        }
+    }
+}
+
+impl KiraTempo {
+    pub fn new(bpm: f64, beats_per_bar: u32, subdivisions: u32) -> Self {
+        Self {
+            bpm,
+            beats_per_bar,
+            subdivisions,
+            ..default()
+        }
+    }
+
+    pub fn with_tween(mut self, tween: Tween) -> Self {
+        self.tween = tween;
+        self
+    }
+
+    /// The clock speed that matches this tempo.
+    pub fn clock_speed(&self) -> ClockSpeed {
+        ClockSpeed::TicksPerMinute(self.bpm * self.ticks_per_beat() as f64)
+    }
+
+    /// The number of clock ticks in a beat.
+    pub fn ticks_per_beat(&self) -> u64 {
+        self.subdivisions.max(1) as u64
+    }
+
+    /// The number of clock ticks in a bar. This can be used as the `every` of a
+    /// [`KiraStartTime::Quantized`] to start a sound on the next bar.
+    ///
+    /// [`KiraStartTime::Quantized`]: crate::KiraStartTime::Quantized
+    pub fn ticks_per_bar(&self) -> u64 {
+        self.ticks_per_beat() * self.beats_per_bar.max(1) as u64
+    }
+
+    /// Converts a clock time in ticks to a musical position.
+    pub fn position(&self, ticks: u64) -> KiraBeatPosition {
+        KiraBeatPosition {
+            bar: ticks / self.ticks_per_bar(),
+            beat: ((ticks / self.ticks_per_beat()) % self.beats_per_bar.max(1) as u64) as u32,
+            subdivision: (ticks % self.ticks_per_beat()) as u32,
+        }
+    }
+
+    /// Converts a musical position to a clock time in ticks.
+    pub fn ticks(&self, position: KiraBeatPosition) -> u64 {
+        position.bar * self.ticks_per_bar()
+            + position.beat as u64 * self.ticks_per_beat()
+            + position.subdivision as u64
+    }
+}
+
+/// The current musical position of a clock with a [`KiraTempo`]. All values are zero based, and
+/// are updated every frame from the clock's time.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KiraBeatPosition {
+    pub bar: u64,
+    pub beat: u32,
+    pub subdivision: u32,
+}
+
+pub(super) fn add_tempo_clocks_sys(
+    mut commands: Commands,
+    query: Query<(Entity, &KiraTempo), Without<KiraClock>>,
+) {
+    for (eid, tempo) in query.iter() {
+        commands
+            .entity(eid)
+            .insert(KiraClock::new(tempo.clock_speed()));
+    }
+}
+
+// The clock handle and its time scale are inserted together once kira has created the clock.
+type TempoClock<'a> = (
+    &'a KiraTempo,
+    &'a mut KiraClock,
+    Option<(&'a mut KiraClockHandle, &'a ClockTimeScale)>,
+);
+
+pub(super) fn sync_tempo_sys(mut query: Query<TempoClock, Changed<KiraTempo>>) {
+    for (tempo, mut clock, handle) in query.iter_mut() {
+        let speed = tempo.clock_speed();
+        // Update the clock without triggering change detection, the tempo's tween should be used
+        // instead of the default one applied when a `KiraClock` changes.
+        clock.bypass_change_detection().speed = speed;
+        if let Some((mut handle, time_scale)) = handle {
+            handle.0.set_speed(time_scale.apply(speed), tempo.tween);
+        }
+    }
+}
+
+pub(super) fn beat_position_sys(
+    mut query: Query<(&KiraTempo, &KiraClockHandle, &mut KiraBeatPosition)>,
+) {
+    for (tempo, handle, mut position) in query.iter_mut() {
+        position.set_if_neq(tempo.position(handle.0.time().ticks));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_splits_ticks_into_bars_beats_and_subdivisions() {
+        // 3/4 with four ticks per beat, so a bar is 12 ticks.
+        let tempo = KiraTempo::new(90.0, 3, 4);
+        assert_eq!(tempo.ticks_per_bar(), 12);
+        let position = KiraBeatPosition {
+            bar: 2,
+            beat: 1,
+            subdivision: 3,
+        };
+        assert_eq!(tempo.position(31), position);
+        assert_eq!(tempo.ticks(position), 31);
+    }
+
+    #[test]
+    fn ticks_and_position_round_trip() {
+        let tempo = KiraTempo::new(120.0, 4, 2);
+        for ticks in 0..100 {
+            assert_eq!(tempo.ticks(tempo.position(ticks)), ticks);
+        }
+    }
+
+    #[test]
+    fn zero_divisions_count_as_one() {
+        let tempo = KiraTempo::new(120.0, 0, 0);
+        assert_eq!(tempo.ticks_per_bar(), 1);
+        assert_eq!(tempo.position(5).bar, 5);
+        assert_eq!(tempo.clock_speed(), ClockSpeed::TicksPerMinute(120.0));
+    }
+}
@@ -4,8 +4,12 @@ use bevy::app::Plugin;
 
 pub use crate::sound::static_sounds::{KiraStaticSoundAsset, StaticSoundFileLoader};
 
+mod cooldowns;
 mod playback;
+mod voices;
+pub use cooldowns::*;
 pub use playback::*;
+pub use voices::*;
 
 pub struct KiraEventsPlugin;
 
@@ -0,0 +1,173 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::{ecs::system::SystemParam, platform::collections::HashMap, prelude::*};
+
+/// Limits how often a sound can be retriggered: at most `max_plays` plays are allowed within any
+/// window of `interval`, further plays are dropped. For example collecting 20 coins in a single
+/// frame with a cooldown of `KiraCooldown::new(Duration::from_millis(50)).with_max_plays(3)` plays
+/// the coin sound three times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KiraCooldown {
+    pub interval: Duration,
+    pub max_plays: usize,
+}
+
+impl KiraCooldown {
+    /// Creates a cooldown that lets a single play through every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            max_plays: 1,
+        }
+    }
+
+    pub fn with_max_plays(mut self, max_plays: usize) -> Self {
+        self.max_plays = max_plays;
+        self
+    }
+}
+
+/// Cooldowns applied to every [`KiraPlaySoundEvent`] of a voice group (see
+/// [`KiraPlaySoundEvent::with_group`]). Cooldowns for a single sound can be set on the event with
+/// [`KiraPlaySoundEvent::with_cooldown`] instead.
+///
+/// [`KiraPlaySoundEvent`]: crate::KiraPlaySoundEvent
+/// [`KiraPlaySoundEvent::with_group`]: crate::KiraPlaySoundEvent::with_group
+/// [`KiraPlaySoundEvent::with_cooldown`]: crate::KiraPlaySoundEvent::with_cooldown
+#[derive(Resource, Debug, Clone, Default)]
+pub struct KiraCooldowns {
+    pub groups: HashMap<String, KiraCooldown>,
+}
+
+impl KiraCooldowns {
+    pub fn with_group(mut self, group: impl Into<String>, cooldown: KiraCooldown) -> Self {
+        self.groups.insert(group.into(), cooldown);
+        self
+    }
+}
+
+// Group cooldowns and per-sound cooldowns are tracked separately so a sound key can't collide with
+// a group name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum CooldownKey {
+    Group(String),
+    Sound(String),
+}
+
+// The keys a play counts towards and the cooldown of each, returned by
+// `CooldownTracker::check` and recorded once the sound actually plays.
+pub(crate) struct CooldownPlay(Vec<(CooldownKey, KiraCooldown)>);
+
+// When each recent play of a key stops counting towards its cooldown, oldest first. Keys without
+// recent plays are removed.
+#[derive(Default)]
+struct CooldownHistory(HashMap<CooldownKey, VecDeque<Duration>>);
+
+impl CooldownHistory {
+    fn prune(&mut self, now: Duration) {
+        self.0.retain(|_, plays| {
+            while plays.front().is_some_and(|expires| *expires <= now) {
+                plays.pop_front();
+            }
+            !plays.is_empty()
+        });
+    }
+
+    fn allows(&self, play: &CooldownPlay) -> bool {
+        play.0
+            .iter()
+            .all(|(key, cooldown)| self.0.get(key).map_or(0, VecDeque::len) < cooldown.max_plays)
+    }
+
+    fn record(&mut self, play: CooldownPlay, now: Duration) {
+        for (key, cooldown) in play.0 {
+            self.0
+                .entry(key)
+                .or_default()
+                .push_back(now + cooldown.interval);
+        }
+    }
+}
+
+#[derive(SystemParam)]
+pub(crate) struct CooldownTracker<'w, 's> {
+    cooldowns: Option<Res<'w, KiraCooldowns>>,
+    time: Res<'w, Time<Real>>,
+    history: Local<'s, CooldownHistory>,
+}
+
+impl CooldownTracker<'_, '_> {
+    /// Forgets the plays whose cooldown is over.
+    pub(crate) fn prune(&mut self) {
+        let now = self.time.elapsed();
+        self.history.prune(now);
+    }
+
+    /// Returns whether a sound can be played given its group and its own cooldown. The play only
+    /// counts once it is passed to [`Self::record`].
+    pub(crate) fn check(
+        &self,
+        group: Option<&str>,
+        sound: Option<&(String, KiraCooldown)>,
+    ) -> Option<CooldownPlay> {
+        let group = group.and_then(|group| {
+            let cooldown = *self.cooldowns.as_ref()?.groups.get(group)?;
+            Some((CooldownKey::Group(group.to_string()), cooldown))
+        });
+        let sound = sound.map(|(key, cooldown)| (CooldownKey::Sound(key.clone()), *cooldown));
+        let play = CooldownPlay(group.into_iter().chain(sound).collect());
+        self.history.allows(&play).then_some(play)
+    }
+
+    pub(crate) fn record(&mut self, play: CooldownPlay) {
+        let now = self.time.elapsed();
+        self.history.record(play, now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(key: &str, cooldown: KiraCooldown) -> CooldownPlay {
+        CooldownPlay(vec![(CooldownKey::Sound(key.to_string()), cooldown)])
+    }
+
+    #[test]
+    fn allows_max_plays_per_interval() {
+        let cooldown = KiraCooldown::new(Duration::from_millis(50)).with_max_plays(2);
+        let mut history = CooldownHistory::default();
+        for _ in 0..2 {
+            assert!(history.allows(&play("coin", cooldown)));
+            history.record(play("coin", cooldown), Duration::ZERO);
+        }
+        assert!(!history.allows(&play("coin", cooldown)));
+        assert!(history.allows(&play("gem", cooldown)));
+        history.prune(Duration::from_millis(49));
+        assert!(!history.allows(&play("coin", cooldown)));
+        history.prune(Duration::from_millis(50));
+        assert!(history.allows(&play("coin", cooldown)));
+        assert!(history.0.is_empty());
+    }
+
+    #[test]
+    fn groups_and_sounds_are_separate_keys() {
+        let cooldown = KiraCooldown::new(Duration::from_secs(1));
+        let mut history = CooldownHistory::default();
+        history.record(play("steps", cooldown), Duration::ZERO);
+        let group = CooldownPlay(vec![(CooldownKey::Group("steps".to_string()), cooldown)]);
+        assert!(history.allows(&group));
+    }
+
+    #[test]
+    fn a_play_is_blocked_by_any_of_its_keys() {
+        let cooldown = KiraCooldown::new(Duration::from_secs(1));
+        let mut history = CooldownHistory::default();
+        history.record(play("hit", cooldown), Duration::ZERO);
+        let both = CooldownPlay(vec![
+            (CooldownKey::Group("impacts".to_string()), cooldown),
+            (CooldownKey::Sound("hit".to_string()), cooldown),
+        ]);
+        assert!(!history.allows(&both));
+    }
+}
@@ -1,6 +1,8 @@
 use std::fmt::Debug;
 use std::fmt::Formatter;
 
+use bevy::ecs::system::SystemParam;
+use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 use kira::sound::PlaybackState;
 
@@ -10,19 +12,24 @@ pub use crate::sound::sound_types::KiraPlayingSound;
 use crate::sound::sound_types::KiraTrackHandle;
 use kira::sound::static_sound::StaticSoundHandle;
 
+use super::cooldowns::{CooldownTracker, KiraCooldown};
+use super::voices::{KiraVoice, VoiceLimiter};
 use crate::KiraContext;
+use crate::plugins::clock::{KiraClockHandle, KiraStartTime};
+use crate::sound::container::{KiraContainerState, KiraSoundContainer};
+use crate::sound::static_sounds::{KiraStaticSoundAsset, KiraStaticSoundData};
 
 #[derive(Component, Default, Reflect)]
 /// This Component represents a collection of all currently playing sounds for an entity.
 /// The sounds can be iterated over using the `static_handles` and `dynamic_handles` methods.
-pub struct KiraPlayingSounds(#[reflect(ignore)] pub(crate) Vec<KiraPlayingSound>);
+pub struct KiraPlayingSounds(#[reflect(ignore)] pub(crate) Vec<KiraVoice>);
 
 impl KiraPlayingSounds {
     /// Returns an iterator over all currently playing static sounds' [`StaticSoundHandle`]s.
     ///
     /// [`StaticSoundHandle`]: https://docs.rs/kira/latest/kira/sound/static_sound/struct.StaticSoundHandle.html
     pub fn static_handles(&self) -> impl Iterator<Item = &StaticSoundHandle> {
-        self.0.iter().filter_map(|sound| match sound {
+        self.0.iter().filter_map(|voice| match &voice.sound {
             KiraPlayingSound::Static(sound) => Some(sound),
             KiraPlayingSound::Dynamic(_) => None,
         })
@@ -33,7 +40,7 @@ impl KiraPlayingSounds {
     where
         T: DynamicSoundHandle + 'static,
     {
-        self.0.iter().filter_map(|sound| match sound {
+        self.0.iter().filter_map(|voice| match &voice.sound {
             KiraPlayingSound::Static(_) => None,
             KiraPlayingSound::Dynamic(dyn_handle) => dyn_handle.as_any().downcast_ref::<T>(),
         })
@@ -58,7 +65,24 @@ pub struct KiraPlaySoundEvent {
     // If set the entity must have a `KiraTrackHandle` component.
     pub(super) track_entity: Option<Entity>,
     /// The sound to play.
-    pub(super) sound: Box<dyn KiraPlayable>,
+    pub(super) sound: SoundSource,
+    /// When the sound should start relative to a clock. If `None` the sound's own settings are
+    /// used.
+    pub(super) start_time: Option<KiraStartTime>,
+    /// The voice group the sound belongs to, see `KiraVoiceLimits`.
+    pub(super) group: Option<String>,
+    /// The priority of the sound when voices are stolen.
+    pub(super) priority: i32,
+    /// A key identifying the sound and the cooldown to apply to it.
+    pub(super) cooldown: Option<(String, KiraCooldown)>,
+    /// Whether the sound follows the `KiraTimeScale` of its track.
+    pub(super) time_scaled: bool,
+}
+
+pub(super) enum SoundSource {
+    Playable(Box<dyn KiraPlayable>),
+    // Resolved to one of the container's sounds when the event is consumed.
+    Container(Handle<KiraSoundContainer>),
 }
 
 impl KiraPlaySoundEvent {
@@ -66,9 +90,80 @@ impl KiraPlaySoundEvent {
         Self {
             entity,
             track_entity,
-            sound: Box::new(sound),
+            sound: SoundSource::Playable(Box::new(sound)),
+            start_time: None,
+            group: None,
+            priority: 0,
+            cooldown: None,
+            time_scaled: true,
+        }
+    }
+
+    /// Plays one of the sounds of a [`KiraSoundContainer`], picked according to the container's
+    /// mode. The event is dropped with a warning if the container or the picked sound has not
+    /// loaded yet.
+    ///
+    /// [`KiraSoundContainer`]: crate::KiraSoundContainer
+    pub fn from_container(
+        entity: Entity,
+        track_entity: Option<Entity>,
+        container: Handle<KiraSoundContainer>,
+    ) -> Self {
+        Self {
+            entity,
+            track_entity,
+            sound: SoundSource::Container(container),
+            start_time: None,
+            group: None,
+            priority: 0,
+            cooldown: None,
+            time_scaled: true,
         }
     }
+
+    /// Schedules the sound to start relative to a clock, see [`KiraStartTime`]. This overrides the
+    /// start time in the sound's own settings.
+    pub fn with_start_time(mut self, start_time: KiraStartTime) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+
+    /// Puts the sound in a voice group so it is subject to the group's limit in
+    /// [`KiraVoiceLimits`].
+    ///
+    /// [`KiraVoiceLimits`]: crate::KiraVoiceLimits
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Sets the priority of the sound for [`KiraVoicePolicy::StealLowestPriority`], higher values
+    /// are more important. The default priority is `0`.
+    ///
+    /// [`KiraVoicePolicy::StealLowestPriority`]: crate::KiraVoicePolicy::StealLowestPriority
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Limits how often the sound can be retriggered. Every event sharing the same `key` shares
+    /// the cooldown, events played while the cooldown is active are dropped. Cooldowns for whole
+    /// groups can be set with the [`KiraCooldowns`] resource.
+    ///
+    /// [`KiraCooldowns`]: crate::KiraCooldowns
+    pub fn with_cooldown(mut self, key: impl Into<String>, cooldown: KiraCooldown) -> Self {
+        self.cooldown = Some((key.into(), cooldown));
+        self
+    }
+
+    /// Keeps the sound playing at its own rate when it is played on a track with
+    /// a [`KiraTimeScale`], for example for UI sounds.
+    ///
+    /// [`KiraTimeScale`]: crate::KiraTimeScale
+    pub fn ignore_time_scale(mut self) -> Self {
+        self.time_scaled = false;
+        self
+    }
 }
 
 impl Debug for KiraPlayingSounds {
@@ -79,39 +174,144 @@ impl Debug for KiraPlayingSounds {
     }
 }
 
-pub(super) fn do_play_sys(
+// Resolves the sounds of `KiraPlaySoundEvent::from_container` events.
+#[derive(SystemParam)]
+pub(crate) struct SoundContainers<'w, 's> {
+    containers: Res<'w, Assets<KiraSoundContainer>>,
+    assets: Res<'w, Assets<KiraStaticSoundAsset>>,
+    states: Local<'s, HashMap<AssetId<KiraSoundContainer>, KiraContainerState>>,
+}
+
+impl SoundContainers<'_, '_> {
+    fn pick(&mut self, handle: &Handle<KiraSoundContainer>) -> Option<Box<dyn KiraPlayable>> {
+        let Some(container) = self.containers.get(handle) else {
+            warn!(
+                "Sound container {:?} is not loaded, skipping sound.",
+                handle
+            );
+            return None;
+        };
+        let state = self.states.entry(handle.id()).or_default();
+        let Some(sound) = state
+            .pick(container)
+            .and_then(|index| self.assets.get(&container.sounds[index]))
+        else {
+            warn!("Sound container {:?} has no loaded sound to play.", handle);
+            return None;
+        };
+        let mut sound = sound.sound.0.clone();
+        container.randomize(&mut sound);
+        Some(Box::new(KiraStaticSoundData(sound)))
+    }
+}
+
+// The playing sounds are stored in `KiraPlayingSounds`, or in `new_voices` for entities that don't
+// have the component yet.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn do_play_sys(
     mut commands: Commands,
     mut kira: NonSendMut<KiraContext>,
-    mut query: Query<(Entity, Option<&mut KiraPlayingSounds>)>,
+    mut query: Query<(Entity, &mut KiraPlayingSounds)>,
+    without_sounds: Query<(), Without<KiraPlayingSounds>>,
     mut track_query: Query<&mut KiraTrackHandle>,
+    clock_query: Query<&KiraClockHandle>,
+    mut containers: SoundContainers,
+    mut voices: VoiceLimiter,
+    mut cooldowns: CooldownTracker,
     mut ev_play: ResMut<Events<KiraPlaySoundEvent>>,
-) -> Result<(), BevyError> {
+) {
+    let mut new_voices: HashMap<Entity, Vec<KiraVoice>> = HashMap::default();
+    cooldowns.prune();
     for event in ev_play.drain() {
-        let mut opt_track = if let Some(track_entity) = event.track_entity {
-            let res = track_query.get_mut(track_entity)?;
-            Some(res)
-        } else {
-            None
+        let Some(cooldown) = cooldowns.check(event.group.as_deref(), event.cooldown.as_ref())
+        else {
+            continue;
+        };
+        let mut sound = match event.sound {
+            SoundSource::Playable(sound) => sound,
+            SoundSource::Container(handle) => match containers.pick(&handle) {
+                Some(sound) => sound,
+                None => continue,
+            },
+        };
+        if let Some(start_time) = event.start_time {
+            let res = start_time
+                .resolve_in(&clock_query)
+                .and_then(|start_time| sound.set_start_time(start_time));
+            if let Err(e) = res {
+                error!("Error scheduling sound: {}", e);
+                continue;
+            }
+        }
+        let mut opt_track = match event.track_entity {
+            Some(track_entity) => match track_query.get_mut(track_entity) {
+                Ok(track) => Some(track),
+                Err(e) => {
+                    error!("Error finding track for sound: {}", e);
+                    continue;
+                }
+            },
+            None => None,
         };
-        let sound_handle = match kira.play(event.sound, opt_track.as_deref_mut()) {
+        let playing = query
+            .iter()
+            .map(|(eid, sounds)| (eid, &sounds.0))
+            .chain(new_voices.iter().map(|(eid, voices)| (*eid, voices)))
+            .flat_map(|(eid, voices)| {
+                voices
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, voice)| ((eid, index), voice))
+            });
+        let Some(stolen) = voices.make_room(event.group.as_deref(), event.priority, playing) else {
+            debug!(
+                "Voice limit reached, not playing sound for entity {:?}.",
+                event.entity
+            );
+            continue;
+        };
+        let volume = sound.volume().unwrap_or_default();
+        let playback_rate = sound.playback_rate();
+        let sound_handle = match kira.play(sound, opt_track.as_deref_mut()) {
             Ok(s) => s,
             Err(e) => {
                 error!("Error playing sound: {}", e);
                 continue;
             }
         };
-
-        if let Ok((eid, active_sounds)) = query.get_mut(event.entity) {
-            match active_sounds {
-                Some(mut sounds) => {
-                    sounds.0.push(sound_handle);
-                }
-                None => {
-                    commands
-                        .entity(eid)
-                        .insert(KiraPlayingSounds(vec![sound_handle]));
-                }
+        // Voices are only stolen once the new sound is playing so a failed play doesn't cut off
+        // another sound.
+        for (eid, index) in stolen {
+            let voice = match new_voices.get_mut(&eid) {
+                Some(new) => new.get_mut(index),
+                None => query
+                    .get_mut(eid)
+                    .ok()
+                    .and_then(|(_, sounds)| sounds.into_inner().0.get_mut(index)),
             };
+            if let Some(voice) = voice {
+                voice.steal(voices.steal_fade());
+            }
+        }
+        cooldowns.record(cooldown);
+        let voice = KiraVoice {
+            sound: sound_handle,
+            track: event.track_entity,
+            group: event.group,
+            priority: event.priority,
+            volume,
+            playback_rate,
+            time_scaled: event.time_scaled,
+            time_scale: 1.0,
+            serial: voices.next_serial(),
+            stolen: false,
+            paused: false,
+        };
+
+        if let Ok((_, mut sounds)) = query.get_mut(event.entity) {
+            sounds.0.push(voice);
+        } else if without_sounds.contains(event.entity) {
+            new_voices.entry(event.entity).or_default().push(voice);
         } else {
             error!(
                 "Failed to associate playing sound handle with entity: {:?}. \
@@ -120,7 +320,9 @@ pub(super) fn do_play_sys(
             );
         }
     }
-    Ok(())
+    for (eid, voices) in new_voices {
+        commands.entity(eid).insert(KiraPlayingSounds(voices));
+    }
 }
 
 pub(super) fn cleanup_inactive_sounds_sys(
@@ -132,16 +334,15 @@ pub(super) fn cleanup_inactive_sounds_sys(
         // notifications notification. This is not yet profiled so may be a premature optimization.
         // note that `any` is short-circuiting so we don't need to worry about the cost iterating
         // over every sound.
-        let needs_cleanup = sounds.0.iter().any(|sound| match &sound {
-            KiraPlayingSound::Static(sound) => sound.state() == PlaybackState::Stopped,
-            KiraPlayingSound::Dynamic(sound) => sound.state() == PlaybackState::Stopped,
-        });
+        let needs_cleanup = sounds
+            .0
+            .iter()
+            .any(|voice| voice.state() == PlaybackState::Stopped);
 
         if needs_cleanup {
-            sounds.0.retain(|sound| match &sound {
-                KiraPlayingSound::Static(sound) => sound.state() != PlaybackState::Stopped,
-                KiraPlayingSound::Dynamic(sound) => sound.state() != PlaybackState::Stopped,
-            });
+            sounds
+                .0
+                .retain(|voice| voice.state() != PlaybackState::Stopped);
         }
         if sounds.0.is_empty() {
             commands.entity(eid).remove::<KiraPlayingSounds>();
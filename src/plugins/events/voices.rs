@@ -0,0 +1,329 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, platform::collections::HashMap, prelude::*};
+use kira::{Decibels, Tween, sound::PlaybackState};
+
+use crate::sound::sound_types::KiraPlayingSound;
+
+/// What happens when a sound is played while its voice limit is already reached.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KiraVoicePolicy {
+    /// Don't play the new sound.
+    RejectNew,
+    /// Stop the voice that started first.
+    #[default]
+    StealOldest,
+    /// Stop the voice that was played with the lowest volume.
+    StealQuietest,
+    /// Stop the voice with the lowest priority, see [`KiraPlaySoundEvent::with_priority`]. If every
+    /// voice has a higher priority than the new sound the new sound is rejected.
+    ///
+    /// [`KiraPlaySoundEvent::with_priority`]: crate::KiraPlaySoundEvent::with_priority
+    StealLowestPriority,
+}
+
+/// The maximum number of voices (sounds playing at the same time) and what to do once it is
+/// reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KiraVoiceLimit {
+    pub max_voices: usize,
+    pub policy: KiraVoicePolicy,
+}
+
+impl KiraVoiceLimit {
+    pub fn new(max_voices: usize, policy: KiraVoicePolicy) -> Self {
+        Self { max_voices, policy }
+    }
+}
+
+/// Limits how many sounds played through [`KiraPlaySoundEvent`]s can play at the same time, both
+/// overall and per group (see [`KiraPlaySoundEvent::with_group`]). Limits are applied when the
+/// events are consumed, before the sound is handed to kira. Voices that are stolen to make room
+/// for a new sound are faded out with `steal_fade`.
+///
+/// Only static sounds can be stolen. Dynamic sounds count towards the limits but when every voice
+/// that could be stolen is a dynamic sound the new sound is rejected instead.
+///
+/// ```
+/// # use bevy_mod_kira::{KiraVoiceLimit, KiraVoiceLimits, KiraVoicePolicy};
+/// let limits = KiraVoiceLimits::default()
+///     .with_global(KiraVoiceLimit::new(64, KiraVoicePolicy::StealLowestPriority))
+///     .with_group("gunshots", KiraVoiceLimit::new(8, KiraVoicePolicy::StealOldest));
+/// ```
+///
+/// [`KiraPlaySoundEvent`]: crate::KiraPlaySoundEvent
+/// [`KiraPlaySoundEvent::with_group`]: crate::KiraPlaySoundEvent::with_group
+#[derive(Resource, Debug, Clone)]
+pub struct KiraVoiceLimits {
+    /// The limit for all sounds, if any.
+    pub global: Option<KiraVoiceLimit>,
+    /// The limits for each group of sounds.
+    pub groups: HashMap<String, KiraVoiceLimit>,
+    /// The tween used to fade out stolen voices.
+    pub steal_fade: Tween,
+}
+
+impl Default for KiraVoiceLimits {
+    fn default() -> Self {
+        Self {
+            global: None,
+            groups: HashMap::default(),
+            steal_fade: Tween {
+                duration: Duration::from_millis(50),
+                ..default()
+            },
+        }
+    }
+}
+
+impl KiraVoiceLimits {
+    pub fn with_global(mut self, limit: KiraVoiceLimit) -> Self {
+        self.global = Some(limit);
+        self
+    }
+
+    pub fn with_group(mut self, group: impl Into<String>, limit: KiraVoiceLimit) -> Self {
+        self.groups.insert(group.into(), limit);
+        self
+    }
+
+    pub fn with_steal_fade(mut self, steal_fade: Tween) -> Self {
+        self.steal_fade = steal_fade;
+        self
+    }
+}
+
+/// A playing sound along with what the voice limits need to know about it.
+pub(crate) struct KiraVoice {
+    pub(crate) sound: KiraPlayingSound,
+    // The entity of the track the sound plays on, `None` for the main track.
+    pub(crate) track: Option<Entity>,
+    pub(crate) group: Option<String>,
+    pub(crate) priority: i32,
+    pub(crate) volume: Decibels,
+    // The playback rate the sound was played with, before any time scale. `None` if it isn't fixed,
+    // for example when it follows a modulator.
+    pub(crate) playback_rate: Option<f64>,
+    // Whether the voice follows the `KiraTimeScale` of its track and the speed last applied to it.
+    pub(crate) time_scaled: bool,
+    pub(crate) time_scale: f64,
+    // Increases with every voice so the oldest voice can be found.
+    pub(crate) serial: u64,
+    pub(crate) stolen: bool,
+    // Whether the voice was paused by `KiraAudioPause` and should be resumed with it.
+    pub(crate) paused: bool,
+}
+
+impl KiraVoice {
+    pub(crate) fn state(&self) -> PlaybackState {
+        match &self.sound {
+            KiraPlayingSound::Static(sound) => sound.state(),
+            KiraPlayingSound::Dynamic(sound) => sound.state(),
+        }
+    }
+
+    // Whether the voice counts towards the limits.
+    fn active(&self) -> bool {
+        !self.stolen
+            && !matches!(
+                self.state(),
+                PlaybackState::Stopping | PlaybackState::Stopped
+            )
+    }
+
+    pub(crate) fn steal(&mut self, fade: Tween) {
+        if let KiraPlayingSound::Static(sound) = &mut self.sound {
+            sound.stop(fade);
+            self.stolen = true;
+        }
+    }
+}
+
+/// Where a voice is stored: the entity it belongs to and its index in that entity's voices.
+pub(crate) type VoiceRef = (Entity, usize);
+
+// What is known about an active voice while looking for room for a new one.
+#[derive(Clone)]
+struct Candidate {
+    voice: VoiceRef,
+    in_group: bool,
+    stealable: bool,
+    priority: i32,
+    volume: Decibels,
+    serial: u64,
+}
+
+#[derive(SystemParam)]
+pub(crate) struct VoiceLimiter<'w, 's> {
+    limits: Option<Res<'w, KiraVoiceLimits>>,
+    serial: Local<'s, u64>,
+}
+
+impl VoiceLimiter<'_, '_> {
+    pub(crate) fn steal_fade(&self) -> Tween {
+        self.limits
+            .as_ref()
+            .map(|limits| limits.steal_fade)
+            .unwrap_or_default()
+    }
+
+    /// A number identifying a new voice, higher numbers are newer voices.
+    pub(crate) fn next_serial(&mut self) -> u64 {
+        *self.serial += 1;
+        *self.serial
+    }
+
+    /// Checks whether a new sound can be played among `voices`. Returns `None` if the sound has to
+    /// be rejected, otherwise the voices that must be stolen to make room for it.
+    pub(crate) fn make_room<'a>(
+        &self,
+        group: Option<&str>,
+        priority: i32,
+        voices: impl Iterator<Item = (VoiceRef, &'a KiraVoice)>,
+    ) -> Option<Vec<VoiceRef>> {
+        let Some(limits) = self.limits.as_ref() else {
+            return Some(vec![]);
+        };
+        let group_limit = group.and_then(|group| limits.groups.get(group));
+        if group_limit.is_none() && limits.global.is_none() {
+            return Some(vec![]);
+        }
+        let candidates = voices
+            .filter(|(_, voice)| voice.active())
+            .map(|(voice_ref, voice)| Candidate {
+                voice: voice_ref,
+                in_group: group.is_some() && voice.group.as_deref() == group,
+                stealable: matches!(voice.sound, KiraPlayingSound::Static(_)),
+                priority: voice.priority,
+                volume: voice.volume,
+                serial: voice.serial,
+            })
+            .collect();
+        limits.victims(group_limit, priority, candidates)
+    }
+}
+
+impl KiraVoicePolicy {
+    // The index of the voice to steal among `victims` to make room for a sound with `priority`.
+    fn victim<'a>(
+        self,
+        victims: impl Iterator<Item = (usize, &'a Candidate)>,
+        priority: i32,
+    ) -> Option<usize> {
+        let victim = match self {
+            KiraVoicePolicy::RejectNew => None,
+            KiraVoicePolicy::StealOldest => victims
+                .filter(|(_, c)| c.stealable)
+                .min_by_key(|(_, c)| c.serial),
+            KiraVoicePolicy::StealQuietest => {
+                victims
+                    .filter(|(_, c)| c.stealable)
+                    .min_by(|(_, a), (_, b)| {
+                        a.volume
+                            .0
+                            .total_cmp(&b.volume.0)
+                            .then(a.serial.cmp(&b.serial))
+                    })
+            }
+            KiraVoicePolicy::StealLowestPriority => victims
+                .filter(|(_, c)| c.stealable && c.priority <= priority)
+                .min_by_key(|(_, c)| (c.priority, c.serial)),
+        };
+        victim.map(|(index, _)| index)
+    }
+}
+
+impl KiraVoiceLimits {
+    // Picks the voices to steal among the active `candidates` so a new sound fits in the group
+    // limit and the global limit, `None` if the sound has to be rejected. A limit that is already
+    // exceeded, for example after it was lowered, has voices stolen until it is met again.
+    fn victims(
+        &self,
+        group_limit: Option<&KiraVoiceLimit>,
+        priority: i32,
+        mut candidates: Vec<Candidate>,
+    ) -> Option<Vec<VoiceRef>> {
+        let mut stolen = vec![];
+        let limits = [
+            group_limit.map(|limit| (limit, true)),
+            self.global.as_ref().map(|limit| (limit, false)),
+        ];
+        for (limit, group_only) in limits.into_iter().flatten() {
+            let in_scope = |c: &Candidate| !group_only || c.in_group;
+            while candidates.iter().filter(|c| in_scope(c)).count() >= limit.max_voices {
+                let victims = candidates.iter().enumerate().filter(|(_, c)| in_scope(c));
+                let index = limit.policy.victim(victims, priority)?;
+                stolen.push(candidates.swap_remove(index).voice);
+            }
+        }
+        Some(stolen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(index: u32, in_group: bool, priority: i32) -> Candidate {
+        Candidate {
+            voice: (Entity::from_raw(index), 0),
+            in_group,
+            stealable: true,
+            priority,
+            volume: Decibels::IDENTITY,
+            serial: index as u64,
+        }
+    }
+
+    fn stolen(victims: Option<Vec<VoiceRef>>) -> Option<Vec<u32>> {
+        victims.map(|victims| victims.iter().map(|(e, _)| e.index()).collect())
+    }
+
+    #[test]
+    fn steals_the_oldest_voice_at_the_limit() {
+        let limits = KiraVoiceLimits::default()
+            .with_global(KiraVoiceLimit::new(2, KiraVoicePolicy::StealOldest));
+        let candidates = vec![candidate(2, false, 0), candidate(1, false, 0)];
+        assert_eq!(stolen(limits.victims(None, 0, candidates)), Some(vec![1]));
+    }
+
+    #[test]
+    fn steals_until_a_lowered_limit_is_met() {
+        let limit = KiraVoiceLimit::new(2, KiraVoicePolicy::StealOldest);
+        let candidates = (1..=4).map(|i| candidate(i, true, 0)).collect();
+        let victims = KiraVoiceLimits::default().victims(Some(&limit), 0, candidates);
+        assert_eq!(stolen(victims), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn group_limit_ignores_other_voices() {
+        let limit = KiraVoiceLimit::new(2, KiraVoicePolicy::StealOldest);
+        let candidates = vec![
+            candidate(1, false, 0),
+            candidate(2, true, 0),
+            candidate(3, true, 0),
+        ];
+        let victims = KiraVoiceLimits::default().victims(Some(&limit), 0, candidates);
+        assert_eq!(stolen(victims), Some(vec![2]));
+    }
+
+    #[test]
+    fn rejects_when_every_voice_is_more_important() {
+        let limits = KiraVoiceLimits::default()
+            .with_global(KiraVoiceLimit::new(2, KiraVoicePolicy::StealLowestPriority));
+        let candidates = vec![candidate(1, false, 5), candidate(2, false, 1)];
+        assert_eq!(stolen(limits.victims(None, 0, candidates.clone())), None);
+        assert_eq!(stolen(limits.victims(None, 1, candidates)), Some(vec![2]));
+    }
+
+    #[test]
+    fn reject_new_never_steals() {
+        let limits = KiraVoiceLimits::default()
+            .with_global(KiraVoiceLimit::new(1, KiraVoicePolicy::RejectNew));
+        assert_eq!(
+            stolen(limits.victims(None, 0, vec![candidate(1, false, 0)])),
+            None
+        );
+        assert_eq!(stolen(limits.victims(None, 0, vec![])), Some(vec![]));
+    }
+}
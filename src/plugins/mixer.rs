@@ -0,0 +1,27 @@
+use bevy::prelude::*;
+
+use super::modulators::apply_bindings_sys;
+
+mod ducking;
+mod snapshot;
+mod volume;
+pub use ducking::*;
+pub use snapshot::*;
+pub use volume::*;
+
+pub(crate) struct KiraMixerPlugin;
+
+impl Plugin for KiraMixerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<KiraMixerSnapshot>()
+            .register_asset_loader(KiraMixerSnapshotLoader)
+            .add_event::<KiraApplySnapshot>()
+            .init_resource::<KiraVolumeSettings>()
+            .add_systems(
+                Update,
+                (ducking_sys, apply_snapshot_sys, apply_volume_settings_sys),
+            )
+            // Bindings set the modulation of the track volumes in `PostUpdate`.
+            .add_systems(PostUpdate, apply_track_volume_sys.after(apply_bindings_sys));
+    }
+}
@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use kira::{Decibels, Tween};
+
+use crate::{KiraTrackLevels, KiraTrackVolume};
+
+/// Lowers the volume of `target_track` while `source_track` is playing, for example to duck the
+/// music under dialogue.
+///
+/// The level of the source track is read from its [`KiraTrackLevels`], so the source track entity
+/// needs a [`KiraMeterHandle`]. Whenever the RMS level of its louder channel rises above `threshold`
+/// the volume of the target track's [`KiraTrackHandle`] is tweened down by `amount` over `attack`,
+/// and back up over `release` once the source is quiet again.
+///
+/// The attenuation is added to the other levels of the target's [`KiraTrackVolume`]. When several
+/// duckings share a target the lowest level wins, and removing a ducking restores its target.
+///
+/// [`KiraMeterHandle`]: crate::KiraMeterHandle
+/// [`KiraTrackHandle`]: crate::KiraTrackHandle
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct KiraDucking {
+    /// The entity with the [`KiraTrackLevels`] of the track to listen to.
+    pub source_track: Entity,
+    /// The entity with the [`KiraTrackVolume`] of the track to duck.
+    pub target_track: Entity,
+    /// How much the target track is attenuated, in decibels.
+    pub amount: f32,
+    /// The source level above which the target track is ducked.
+    pub threshold: Decibels,
+    /// How long the target track takes to duck.
+    pub attack: Duration,
+    /// How long the target track takes to recover once the source is quiet.
+    pub release: Duration,
+    ducked: bool,
+}
+
+impl KiraDucking {
+    pub fn new(source_track: Entity, target_track: Entity, amount: f32) -> Self {
+        Self {
+            source_track,
+            target_track,
+            amount,
+            threshold: Decibels(-40.0),
+            attack: Duration::from_millis(100),
+            release: Duration::from_millis(500),
+            ducked: false,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: Decibels) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_attack(mut self, attack: Duration) -> Self {
+        self.attack = attack;
+        self
+    }
+
+    pub fn with_release(mut self, release: Duration) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// Whether the target track is currently ducked.
+    pub fn ducked(&self) -> bool {
+        self.ducked
+    }
+}
+
+// The attenuation of a target track, from the lowest level of the duckings on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TargetDucking {
+    volume: Decibels,
+    attack: Duration,
+    release: Duration,
+}
+
+impl TargetDucking {
+    fn add(&mut self, ducking: &KiraDucking) {
+        let volume = ducking.volume();
+        if volume.0 < self.volume.0 {
+            self.volume = volume;
+            self.attack = ducking.attack;
+        }
+        self.release = self.release.max(ducking.release);
+    }
+}
+
+impl KiraDucking {
+    fn volume(&self) -> Decibels {
+        if self.ducked {
+            Decibels(-self.amount)
+        } else {
+            Decibels::IDENTITY
+        }
+    }
+}
+
+// Combines the duckings of every target, so several duckings on one track don't overwrite each
+// other. Targets ducked on the last run that no ducking refers to anymore, because it was removed
+// or despawned, are restored over the release of their last ducking.
+pub(super) fn ducking_sys(
+    mut query: Query<&mut KiraDucking>,
+    sources: Query<&KiraTrackLevels>,
+    mut tracks: Query<&mut KiraTrackVolume>,
+    mut ducked_targets: Local<EntityHashMap<Duration>>,
+) {
+    let mut targets = EntityHashMap::<TargetDucking>::default();
+    for mut ducking in query.iter_mut() {
+        if let Ok(source) = sources.get(ducking.source_track) {
+            let level = source.rms[0].0.max(source.rms[1].0);
+            let ducked = level > ducking.threshold.0;
+            if ducked != ducking.ducked {
+                ducking.ducked = ducked;
+            }
+        }
+        targets
+            .entry(ducking.target_track)
+            .or_insert(TargetDucking {
+                volume: Decibels::IDENTITY,
+                attack: Duration::ZERO,
+                release: Duration::ZERO,
+            })
+            .add(&ducking);
+    }
+    for (eid, release) in ducked_targets.drain() {
+        if !targets.contains_key(&eid) {
+            if let Ok(mut target) = tracks.get_mut(eid) {
+                set_ducking(&mut target, Decibels::IDENTITY, release);
+            }
+        }
+    }
+    for (&eid, ducking) in targets.iter() {
+        let Ok(mut target) = tracks.get_mut(eid) else {
+            continue;
+        };
+        let duration = if ducking.volume.0 < target.ducking.0 {
+            ducking.attack
+        } else {
+            ducking.release
+        };
+        set_ducking(&mut target, ducking.volume, duration);
+        if ducking.volume != Decibels::IDENTITY {
+            ducked_targets.insert(eid, ducking.release);
+        }
+    }
+}
+
+fn set_ducking(target: &mut Mut<KiraTrackVolume>, volume: Decibels, duration: Duration) {
+    if target.ducking != volume {
+        target.ducking = volume;
+        target.tween = Tween {
+            duration,
+            ..default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loud() -> KiraTrackLevels {
+        KiraTrackLevels {
+            rms: [Decibels(-6.0), Decibels(-60.0)],
+            ..default()
+        }
+    }
+
+    fn setup() -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.add_systems(Update, ducking_sys);
+        let source = app.world_mut().spawn(KiraTrackLevels::default()).id();
+        let target = app.world_mut().spawn(KiraTrackVolume::default()).id();
+        (app, source, target)
+    }
+
+    fn ducking(app: &App, target: Entity) -> Decibels {
+        app.world().get::<KiraTrackVolume>(target).unwrap().ducking
+    }
+
+    #[test]
+    fn ducks_while_source_is_loud() {
+        let (mut app, source, target) = setup();
+        let eid = app
+            .world_mut()
+            .spawn(KiraDucking::new(source, target, 12.0))
+            .id();
+        app.update();
+        assert_eq!(ducking(&app, target), Decibels::IDENTITY);
+        app.world_mut().entity_mut(source).insert(loud());
+        app.update();
+        assert_eq!(ducking(&app, target), Decibels(-12.0));
+        assert!(app.world().get::<KiraDucking>(eid).unwrap().ducked());
+        app.world_mut()
+            .entity_mut(source)
+            .insert(KiraTrackLevels::default());
+        app.update();
+        assert_eq!(ducking(&app, target), Decibels::IDENTITY);
+    }
+
+    #[test]
+    fn lowest_ducking_of_a_target_wins() {
+        let (mut app, source, target) = setup();
+        let quiet_source = app.world_mut().spawn(KiraTrackLevels::default()).id();
+        app.world_mut().entity_mut(source).insert(loud());
+        app.world_mut().spawn_batch([
+            KiraDucking::new(source, target, 6.0),
+            KiraDucking::new(source, target, 18.0),
+            KiraDucking::new(quiet_source, target, 24.0),
+        ]);
+        app.update();
+        app.update();
+        assert_eq!(ducking(&app, target), Decibels(-18.0));
+    }
+
+    #[test]
+    fn removed_ducking_restores_target() {
+        let (mut app, source, target) = setup();
+        app.world_mut().entity_mut(source).insert(loud());
+        let removed = app
+            .world_mut()
+            .spawn(KiraDucking::new(source, target, 12.0))
+            .id();
+        let despawned = app
+            .world_mut()
+            .spawn(KiraDucking::new(source, target, 6.0))
+            .id();
+        app.update();
+        assert_eq!(ducking(&app, target), Decibels(-12.0));
+        app.world_mut().entity_mut(removed).remove::<KiraDucking>();
+        app.update();
+        assert_eq!(ducking(&app, target), Decibels(-6.0));
+        app.world_mut().despawn(despawned);
+        app.update();
+        assert_eq!(ducking(&app, target), Decibels::IDENTITY);
+    }
+}
@@ -0,0 +1,226 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use kira::{Decibels, Tween, modulator::tweener::TweenerHandle};
+use serde::Deserialize;
+
+use crate::{
+    KiraTrackHandle, KiraTrackVolume,
+    sound::{sound_types::KiraSendTrackHandle, static_sounds::KiraError},
+};
+
+/// The settings a [`KiraMixerSnapshot`] applies to a single track.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct KiraTrackSnapshot {
+    /// The [`Name`] of the entity holding the track's [`KiraTrackHandle`].
+    pub track: String,
+    /// The snapshot's level of the track's [`KiraTrackVolume`] in decibels, left unchanged if
+    /// `None`.
+    #[serde(default)]
+    pub volume: Option<f32>,
+    /// The volume in decibels of the track's routes to send tracks, keyed by the [`Name`] of the
+    /// entity holding the send track's [`KiraSendTrackHandle`].
+    #[serde(default)]
+    pub sends: HashMap<String, f32>,
+}
+
+/// A named state of the mixer, for example "paused", "underwater" or "cutscene", that can be
+/// transitioned to with a [`KiraApplySnapshot`] event.
+///
+/// Tracks are looked up by the [`Name`] of their entity. Effect parameters are set through
+/// [`KiraMixerParameter`]s which are looked up by their name as well. Snapshots can be added to
+/// `Assets<KiraMixerSnapshot>` directly or loaded from `.snapshot.ron` files, for example:
+///
+/// ```ron
+/// (
+///     name: "underwater",
+///     tracks: [
+///         (track: "music", volume: Some(-6.0)),
+///         (track: "sfx", volume: Some(-12.0), sends: {"reverb": -3.0}),
+///     ],
+///     parameters: {"lowpass_cutoff": 800.0},
+/// )
+/// ```
+///
+/// Only snapshots that are loaded can be applied, so keep a handle to them around.
+#[derive(Asset, TypePath, Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct KiraMixerSnapshot {
+    pub name: String,
+    #[serde(default)]
+    pub tracks: Vec<KiraTrackSnapshot>,
+    /// The values of [`KiraMixerParameter`]s, keyed by parameter name.
+    #[serde(default)]
+    pub parameters: HashMap<String, f64>,
+}
+
+/// An effect parameter (or any other value) that mixer snapshots can tween. Create a kira tweener
+/// with `AudioManager::add_modulator`, use it as the parameter's value with
+/// `Value::from_modulator` when building the effect and spawn its handle with this component.
+#[derive(Component)]
+pub struct KiraMixerParameter {
+    pub name: String,
+    pub handle: TweenerHandle,
+}
+
+impl KiraMixerParameter {
+    pub fn new(name: impl Into<String>, handle: TweenerHandle) -> Self {
+        Self {
+            name: name.into(),
+            handle,
+        }
+    }
+}
+
+/// Write this event to transition the mixer to the [`KiraMixerSnapshot`] with the given name.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct KiraApplySnapshot {
+    pub name: String,
+    pub tween: Tween,
+}
+
+impl KiraApplySnapshot {
+    pub fn new(name: impl Into<String>, tween: Tween) -> Self {
+        Self {
+            name: name.into(),
+            tween,
+        }
+    }
+}
+
+pub struct KiraMixerSnapshotLoader;
+
+impl AssetLoader for KiraMixerSnapshotLoader {
+    type Asset = KiraMixerSnapshot;
+    type Settings = ();
+    type Error = KiraError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, KiraError> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["snapshot.ron"]
+    }
+}
+
+pub(super) fn apply_snapshot_sys(
+    mut ev_apply: EventReader<KiraApplySnapshot>,
+    snapshots: Res<Assets<KiraMixerSnapshot>>,
+    track_names: Query<(Entity, &Name), With<KiraTrackHandle>>,
+    mut tracks: Query<(&mut KiraTrackHandle, &mut KiraTrackVolume)>,
+    sends: Query<(Entity, &Name, &KiraSendTrackHandle)>,
+    mut parameters: Query<&mut KiraMixerParameter>,
+) {
+    for event in ev_apply.read() {
+        let Some((_, snapshot)) = snapshots.iter().find(|(_, s)| s.name == event.name) else {
+            error!("No mixer snapshot named \"{}\" is loaded.", event.name);
+            continue;
+        };
+        let track_entities: HashMap<&str, Entity> = track_names
+            .iter()
+            .map(|(eid, name)| (name.as_str(), eid))
+            .collect();
+        let send_entities: HashMap<&str, Entity> = sends
+            .iter()
+            .map(|(eid, name, _)| (name.as_str(), eid))
+            .collect();
+        for settings in snapshot.tracks.iter() {
+            let Some((mut track, mut track_volume)) = track_entities
+                .get(settings.track.as_str())
+                .and_then(|eid| tracks.get_mut(*eid).ok())
+            else {
+                warn!(
+                    "Mixer snapshot \"{}\" has settings for unknown track \"{}\".",
+                    snapshot.name, settings.track
+                );
+                continue;
+            };
+            if let Some(volume) = settings.volume {
+                track_volume.snapshot = Decibels(volume);
+                track_volume.tween = event.tween;
+            }
+            for (send_name, volume) in settings.sends.iter() {
+                let Some((_, _, send)) = send_entities
+                    .get(send_name.as_str())
+                    .and_then(|eid| sends.get(*eid).ok())
+                else {
+                    warn!(
+                        "Mixer snapshot \"{}\" has settings for unknown send track \"{}\".",
+                        snapshot.name, send_name
+                    );
+                    continue;
+                };
+                if let Err(e) = track.0.set_send(&send.0, Decibels(*volume), event.tween) {
+                    error!(
+                        "Track \"{}\" has no route to send track \"{}\": {}",
+                        settings.track, send_name, e
+                    );
+                }
+            }
+        }
+        for mut parameter in parameters.iter_mut() {
+            if let Some(value) = snapshot.parameters.get(&parameter.name) {
+                parameter.handle.set(*value, event.tween);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kira::{
+        AudioManager, AudioManagerSettings, backend::mock::MockBackend, track::TrackBuilder,
+    };
+
+    use super::*;
+
+    // The example from the documentation of `KiraMixerSnapshot`.
+    const UNDERWATER: &str = r#"(
+        name: "underwater",
+        tracks: [
+            (track: "music", volume: Some(-6.0)),
+            (track: "sfx", volume: Some(-12.0), sends: {"reverb": -3.0}),
+        ],
+        parameters: {"lowpass_cutoff": 800.0},
+    )"#;
+
+    #[test]
+    fn documented_snapshot_applies_to_named_tracks() {
+        let snapshot: KiraMixerSnapshot = ron::de::from_str(UNDERWATER).unwrap();
+        assert_eq!(snapshot.name, "underwater");
+        assert_eq!(snapshot.tracks[1].sends.get("reverb"), Some(&-3.0));
+        assert_eq!(snapshot.parameters.get("lowpass_cutoff"), Some(&800.0));
+
+        let mut manager =
+            AudioManager::<MockBackend>::new(AudioManagerSettings::default()).unwrap();
+        let mut app = App::new();
+        app.init_resource::<Assets<KiraMixerSnapshot>>()
+            .add_event::<KiraApplySnapshot>()
+            .add_systems(Update, apply_snapshot_sys);
+        app.world_mut()
+            .resource_mut::<Assets<KiraMixerSnapshot>>()
+            .add(snapshot);
+        let mut track = |name: &'static str| {
+            let handle = manager.add_sub_track(TrackBuilder::new()).unwrap();
+            app.world_mut()
+                .spawn((Name::new(name), KiraTrackHandle(handle)))
+                .id()
+        };
+        let (music, ui) = (track("music"), track("ui"));
+        app.world_mut()
+            .send_event(KiraApplySnapshot::new("underwater", Tween::default()));
+        app.update();
+        let volume = |eid| app.world().get::<KiraTrackVolume>(eid).unwrap().snapshot;
+        assert_eq!(volume(music), Decibels(-6.0));
+        assert_eq!(volume(ui), Decibels::IDENTITY);
+    }
+}
@@ -0,0 +1,196 @@
+use std::path::Path;
+
+use anyhow::Error;
+use bevy::prelude::*;
+use kira::{Decibels, Mapping, Tween, Value, modulator::ModulatorId};
+use serde::{Deserialize, Serialize};
+
+use crate::{KiraContext, KiraTrackHandle, util::amplitude_to_decibels};
+
+/// The volume of a track, made up of everything in the crate that sets it: the volume setting of
+/// its [`KiraVolumeBus`], [`KiraMixerSnapshot`]s, [`KiraDucking`], the intensity of a
+/// [`KiraMusicLayers`] layer and a [`KiraModulatedParameter::TrackVolume`] binding. Their levels in
+/// decibels are added up and set on the entity's [`KiraTrackHandle`] together, so none of them
+/// overwrites the others.
+///
+/// [`KiraMixerSnapshot`]: crate::KiraMixerSnapshot
+/// [`KiraDucking`]: crate::KiraDucking
+/// [`KiraMusicLayers`]: crate::KiraMusicLayers
+/// [`KiraModulatedParameter::TrackVolume`]: crate::KiraModulatedParameter::TrackVolume
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct KiraTrackVolume {
+    pub(crate) bus: Decibels,
+    pub(crate) snapshot: Decibels,
+    pub(crate) ducking: Decibels,
+    pub(crate) intensity: Decibels,
+    // The modulator the volume follows, the other levels shift its output range.
+    pub(crate) modulation: Option<(ModulatorId, Mapping<f64>)>,
+    // The tween of the last level that changed.
+    pub(crate) tween: Tween,
+    // Until something sets a level the track keeps the volume it was built with.
+    applied: bool,
+}
+
+impl KiraTrackVolume {
+    pub(crate) fn with_intensity(intensity: Decibels) -> Self {
+        Self {
+            intensity,
+            ..default()
+        }
+    }
+
+    /// The sum of the levels, without the modulation.
+    pub fn decibels(&self) -> Decibels {
+        Decibels(self.bus.0 + self.snapshot.0 + self.ducking.0 + self.intensity.0)
+    }
+
+    fn value(&self) -> Value<Decibels> {
+        let offset = self.decibels().0;
+        match self.modulation {
+            Some((id, mapping)) => Value::from_modulator(
+                id,
+                Mapping {
+                    input_range: mapping.input_range,
+                    output_range: (
+                        Decibels(mapping.output_range.0 as f32 + offset),
+                        Decibels(mapping.output_range.1 as f32 + offset),
+                    ),
+                    easing: mapping.easing,
+                },
+            ),
+            None => Value::Fixed(Decibels(offset)),
+        }
+    }
+}
+
+/// Marks the entity holding a [`KiraTrackHandle`] as the bus track that a category of
+/// [`KiraVolumeSettings`] controls. Route every sound of the category through this track, for
+/// example by adding the category's tracks as sub-tracks of the bus.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KiraVolumeBus {
+    Music,
+    Sfx,
+    Voice,
+}
+
+/// The volume levels of a typical settings menu. `master` controls the main track, the other levels
+/// control the tracks of the entities marked with the matching [`KiraVolumeBus`].
+///
+/// Levels are linear amplitudes from `0.0` (silent) to `1.0` (unchanged) so they map directly to
+/// a slider, they are converted to decibels when applied. The settings are applied whenever the
+/// resource changes and can be saved to and loaded from a RON file.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KiraVolumeSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub voice: f32,
+}
+
+impl Default for KiraVolumeSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+            voice: 1.0,
+        }
+    }
+}
+
+impl KiraVolumeSettings {
+    /// The level of the given bus.
+    pub fn level(&self, bus: KiraVolumeBus) -> f32 {
+        match bus {
+            KiraVolumeBus::Music => self.music,
+            KiraVolumeBus::Sfx => self.sfx,
+            KiraVolumeBus::Voice => self.voice,
+        }
+    }
+
+    /// Converts a level of these settings to the volume it is applied with.
+    pub fn decibels(level: f32) -> Decibels {
+        amplitude_to_decibels(level.clamp(0.0, 1.0))
+    }
+
+    /// Reads settings previously written with [`KiraVolumeSettings::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&contents)?)
+    }
+
+    /// Writes the settings to a RON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+pub(super) fn apply_volume_settings_sys(
+    mut kira: NonSendMut<KiraContext>,
+    settings: Res<KiraVolumeSettings>,
+    mut buses: Query<(
+        Ref<KiraVolumeBus>,
+        Ref<KiraTrackHandle>,
+        &mut KiraTrackVolume,
+    )>,
+) {
+    if settings.is_changed() {
+        match kira.get_manager() {
+            Ok(manager) => manager.main_track().set_volume(
+                KiraVolumeSettings::decibels(settings.master),
+                Tween::default(),
+            ),
+            Err(e) => error!("Error applying the master volume: {}", e),
+        }
+    }
+    for (bus, track, mut volume) in buses.iter_mut() {
+        // Buses and tracks added since the last change still need their volume.
+        if settings.is_changed() || bus.is_changed() || track.is_added() {
+            let bus = KiraVolumeSettings::decibels(settings.level(*bus));
+            if volume.bus != bus {
+                volume.bus = bus;
+                volume.tween = Tween::default();
+            }
+        }
+    }
+}
+
+pub(super) fn apply_track_volume_sys(
+    mut tracks: Query<(&mut KiraTrackHandle, &mut KiraTrackVolume)>,
+) {
+    for (mut track, mut volume) in tracks.iter_mut() {
+        // A new handle for the same entity needs the volume again.
+        if !(volume.is_changed() || track.is_added() && volume.applied) {
+            continue;
+        }
+        if !volume.applied && *volume == KiraTrackVolume::default() {
+            continue;
+        }
+        let volume = volume.bypass_change_detection();
+        volume.applied = true;
+        track
+            .bypass_change_detection()
+            .0
+            .set_volume(volume.value(), volume.tween);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_add_up() {
+        let volume = KiraTrackVolume {
+            bus: Decibels(-6.0),
+            snapshot: Decibels(-3.0),
+            ducking: Decibels(-12.0),
+            ..default()
+        };
+        assert_eq!(volume.decibels(), Decibels(-21.0));
+        assert_eq!(volume.value(), Value::Fixed(Decibels(-21.0)));
+    }
+}
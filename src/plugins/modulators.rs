@@ -0,0 +1,26 @@
+use bevy::prelude::*;
+
+use crate::effects::BuiltinEffectSync;
+
+mod binding;
+mod parameter;
+mod sources;
+pub use binding::*;
+pub use parameter::*;
+pub use sources::*;
+
+pub(crate) struct KiraModulatorsPlugin;
+
+impl Plugin for KiraModulatorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, add_modulators_sys).add_systems(
+            PostUpdate,
+            (
+                (parameter_bindings_sys, sync_modulators_sys).chain(),
+                remove_parameter_bindings_sys,
+                remove_modulators_sys,
+                apply_bindings_sys.after(BuiltinEffectSync),
+            ),
+        );
+    }
+}
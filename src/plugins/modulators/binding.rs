@@ -0,0 +1,312 @@
+use bevy::{ecs::query::QueryData, platform::collections::HashMap, prelude::*};
+use kira::{
+    Decibels, Easing, Mapping, Mix, Panning, PlaybackRate, Tween, Value, modulator::ModulatorId,
+};
+
+use crate::{
+    KiraCompressor, KiraDelay, KiraDistortion, KiraEq, KiraFilter, KiraPanningControl,
+    KiraPlayingSounds, KiraReverb, KiraTrackVolume, KiraVolumeControl,
+    effects::{BuiltinEffect, BuiltinEffectHandle},
+    sound::sound_types::KiraPlayingSound,
+};
+
+use super::sources::ModulatorHandle;
+
+/// A parameter of an entity that a [`KiraModulatorBinding`] can link to a modulator. The output
+/// range of the binding's mapping is in the unit of the parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KiraModulatedParameter {
+    /// The volume in decibels of the entity's [`KiraTrackHandle`], added to the other levels of its
+    /// [`KiraTrackVolume`].
+    ///
+    /// [`KiraTrackHandle`]: crate::KiraTrackHandle
+    TrackVolume,
+    /// The playback rate of every static sound in the entity's [`KiraPlayingSounds`], as a factor
+    /// of the normal speed. Replaces the rate the sounds were played with and their
+    /// [`KiraTimeScale`].
+    ///
+    /// [`KiraTimeScale`]: crate::KiraTimeScale
+    PlaybackRate,
+    /// The cutoff frequency in Hz of the entity's [`KiraFilter`].
+    FilterCutoff,
+    FilterResonance,
+    FilterMix,
+    ReverbFeedback,
+    ReverbDamping,
+    ReverbMix,
+    /// The feedback in decibels of the entity's [`KiraDelay`].
+    DelayFeedback,
+    DelayMix,
+    /// The drive in decibels of the entity's [`KiraDistortion`].
+    DistortionDrive,
+    DistortionMix,
+    CompressorThreshold,
+    CompressorRatio,
+    /// The makeup gain in decibels of the entity's [`KiraCompressor`].
+    CompressorMakeupGain,
+    CompressorMix,
+    /// The gain in decibels of the band with this index in the entity's [`KiraEq`].
+    EqGain(usize),
+    /// The frequency in Hz of the band with this index in the entity's [`KiraEq`].
+    EqFrequency(usize),
+    /// The panning of the entity's [`KiraPanningControl`], from `-1.0` (left) to `1.0` (right).
+    PanningControl,
+    /// The volume in decibels of the entity's [`KiraVolumeControl`].
+    VolumeControl,
+}
+
+/// Links a parameter of `target` to the modulator of the `modulator` entity, created for its
+/// [`KiraLfo`] or [`KiraTweener`]. The mapping converts the modulator's value to the parameter's
+/// value, by default the modulator's `0.0..1.0` becomes `0.0..1.0`.
+///
+/// The parameter follows the modulator on the audio thread until the binding is removed or changed,
+/// the parameter is then set back to the value of its component (`0dB` for the track volume and
+/// the played rate for sounds). Changes to the parameter's component while it is bound only apply
+/// to its other parameters.
+///
+/// [`KiraLfo`]: crate::KiraLfo
+/// [`KiraTweener`]: crate::KiraTweener
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraModulatorBinding {
+    pub modulator: Entity,
+    pub target: Entity,
+    pub parameter: KiraModulatedParameter,
+    pub mapping: Mapping<f64>,
+}
+
+impl KiraModulatorBinding {
+    pub fn new(modulator: Entity, target: Entity, parameter: KiraModulatedParameter) -> Self {
+        Self {
+            modulator,
+            target,
+            parameter,
+            mapping: Mapping {
+                input_range: (0.0, 1.0),
+                output_range: (0.0, 1.0),
+                easing: Easing::Linear,
+            },
+        }
+    }
+
+    /// Maps the modulator's values from `input` to the parameter's values in `output`. Values
+    /// outside of `input` are clamped.
+    pub fn with_mapping(mut self, input: (f64, f64), output: (f64, f64)) -> Self {
+        self.mapping.input_range = input;
+        self.mapping.output_range = output;
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.mapping.easing = easing;
+        self
+    }
+}
+
+// The modulator a parameter follows, `None` to set the parameter back to its own value.
+type Modulation = Option<(ModulatorId, Mapping<f64>)>;
+
+fn value<T>((id, mapping): (ModulatorId, Mapping<f64>), convert: impl Fn(f64) -> T) -> Value<T> {
+    Value::from_modulator(
+        id,
+        Mapping {
+            input_range: mapping.input_range,
+            output_range: (
+                convert(mapping.output_range.0),
+                convert(mapping.output_range.1),
+            ),
+            easing: mapping.easing,
+        },
+    )
+}
+
+fn decibels(value: f64) -> Decibels {
+    Decibels(value as f32)
+}
+
+fn mix(value: f64) -> Mix {
+    Mix(value as f32)
+}
+
+#[derive(QueryData)]
+#[query_data(mutable)]
+pub(crate) struct ModulationTarget {
+    track_volume: Option<&'static mut KiraTrackVolume>,
+    sounds: Option<&'static mut KiraPlayingSounds>,
+    eq: Option<ModulatedEffect<KiraEq>>,
+    filter: Option<ModulatedEffect<KiraFilter>>,
+    compressor: Option<ModulatedEffect<KiraCompressor>>,
+    distortion: Option<ModulatedEffect<KiraDistortion>>,
+    delay: Option<ModulatedEffect<KiraDelay>>,
+    reverb: Option<ModulatedEffect<KiraReverb>>,
+    panning: Option<ModulatedEffect<KiraPanningControl>>,
+    volume: Option<ModulatedEffect<KiraVolumeControl>>,
+}
+
+type ModulatedEffect<T> = (Ref<'static, T>, &'static mut BuiltinEffectHandle<T>);
+
+// Points the parameter of the effect at the modulator again when `force` is set or the effect was
+// just created or synced with its component, which overwrites the modulation.
+fn modulate_effect<T: BuiltinEffect>(
+    effect: Option<(Ref<T>, Mut<BuiltinEffectHandle<T>>)>,
+    modulation: Modulation,
+    force: bool,
+    set: impl FnOnce(&mut T::Handle, (ModulatorId, Mapping<f64>)),
+) {
+    let Some((component, mut handle)) = effect else {
+        return;
+    };
+    match modulation {
+        Some(modulation) => {
+            if force || component.is_changed() || handle.is_added() {
+                set(&mut handle.bypass_change_detection().0, modulation);
+            }
+        }
+        None => component.apply(&mut handle.bypass_change_detection().0, Tween::default()),
+    }
+}
+
+fn modulate(
+    target: ModulationTargetItem,
+    parameter: KiraModulatedParameter,
+    modulation: Modulation,
+    force: bool,
+) {
+    use KiraModulatedParameter as P;
+    let tween = Tween::default();
+    match parameter {
+        P::TrackVolume => {
+            let Some(mut volume) = target.track_volume else {
+                return;
+            };
+            if volume.modulation != modulation {
+                volume.modulation = modulation;
+                volume.tween = tween;
+            }
+        }
+        P::PlaybackRate => {
+            let Some(mut sounds) = target.sounds else {
+                return;
+            };
+            // Also catches sounds played since the last update.
+            if modulation.is_some() && !force && !sounds.is_changed() {
+                return;
+            }
+            for voice in sounds.bypass_change_detection().0.iter_mut() {
+                let KiraPlayingSound::Static(sound) = &mut voice.sound else {
+                    continue;
+                };
+                match (modulation, voice.playback_rate) {
+                    (Some(modulation), _) => {
+                        sound.set_playback_rate(value(modulation, PlaybackRate), tween)
+                    }
+                    (None, Some(rate)) => {
+                        sound.set_playback_rate(PlaybackRate(rate * voice.time_scale), tween)
+                    }
+                    // The rate the sound was played with can't be restored.
+                    (None, None) => {}
+                }
+            }
+        }
+        P::FilterCutoff => modulate_effect(target.filter, modulation, force, |handle, m| {
+            handle.set_cutoff(value(m, |v| v), tween)
+        }),
+        P::FilterResonance => modulate_effect(target.filter, modulation, force, |handle, m| {
+            handle.set_resonance(value(m, |v| v), tween)
+        }),
+        P::FilterMix => modulate_effect(target.filter, modulation, force, |handle, m| {
+            handle.set_mix(value(m, mix), tween)
+        }),
+        P::ReverbFeedback => modulate_effect(target.reverb, modulation, force, |handle, m| {
+            handle.set_feedback(value(m, |v| v), tween)
+        }),
+        P::ReverbDamping => modulate_effect(target.reverb, modulation, force, |handle, m| {
+            handle.set_damping(value(m, |v| v), tween)
+        }),
+        P::ReverbMix => modulate_effect(target.reverb, modulation, force, |handle, m| {
+            handle.set_mix(value(m, mix), tween)
+        }),
+        P::DelayFeedback => modulate_effect(target.delay, modulation, force, |handle, m| {
+            handle.set_feedback(value(m, decibels), tween)
+        }),
+        P::DelayMix => modulate_effect(target.delay, modulation, force, |handle, m| {
+            handle.set_mix(value(m, mix), tween)
+        }),
+        P::DistortionDrive => modulate_effect(target.distortion, modulation, force, |handle, m| {
+            handle.set_drive(value(m, decibels), tween)
+        }),
+        P::DistortionMix => modulate_effect(target.distortion, modulation, force, |handle, m| {
+            handle.set_mix(value(m, mix), tween)
+        }),
+        P::CompressorThreshold => {
+            modulate_effect(target.compressor, modulation, force, |handle, m| {
+                handle.set_threshold(value(m, |v| v), tween)
+            })
+        }
+        P::CompressorRatio => modulate_effect(target.compressor, modulation, force, |handle, m| {
+            handle.set_ratio(value(m, |v| v), tween)
+        }),
+        P::CompressorMakeupGain => {
+            modulate_effect(target.compressor, modulation, force, |handle, m| {
+                handle.set_makeup_gain(value(m, decibels), tween)
+            })
+        }
+        P::CompressorMix => modulate_effect(target.compressor, modulation, force, |handle, m| {
+            handle.set_mix(value(m, mix), tween)
+        }),
+        P::EqGain(band) => modulate_effect(target.eq, modulation, force, |handle, m| {
+            if let Some(handle) = handle.get_mut(band) {
+                handle.set_gain(value(m, decibels), tween);
+            }
+        }),
+        P::EqFrequency(band) => modulate_effect(target.eq, modulation, force, |handle, m| {
+            if let Some(handle) = handle.get_mut(band) {
+                handle.set_frequency(value(m, |v| v), tween);
+            }
+        }),
+        P::PanningControl => modulate_effect(target.panning, modulation, force, |handle, m| {
+            handle.set_panning(value(m, |v| Panning(v as f32)), tween)
+        }),
+        P::VolumeControl => modulate_effect(target.volume, modulation, force, |handle, m| {
+            handle.set_volume(value(m, decibels), tween)
+        }),
+    }
+}
+
+pub(crate) fn apply_bindings_sys(
+    bindings: Query<(Entity, Ref<KiraModulatorBinding>)>,
+    modulators: Query<Ref<ModulatorHandle>>,
+    mut targets: Query<ModulationTarget>,
+    // The target and parameter each binding was last applied to.
+    mut applied: Local<HashMap<Entity, (Entity, KiraModulatedParameter)>>,
+) {
+    // Set the parameters of removed bindings back first, as another binding may now take over.
+    applied.retain(|eid, (target, parameter)| {
+        let unchanged = bindings
+            .get(*eid)
+            .is_ok_and(|(_, binding)| (binding.target, binding.parameter) == (*target, *parameter));
+        if !unchanged {
+            if let Ok(target) = targets.get_mut(*target) {
+                modulate(target, *parameter, None, true);
+            }
+        }
+        unchanged
+    });
+    for (eid, binding) in bindings.iter() {
+        let Ok(modulator) = modulators.get(binding.modulator) else {
+            continue;
+        };
+        let Ok(target) = targets.get_mut(binding.target) else {
+            continue;
+        };
+        let new = applied
+            .insert(eid, (binding.target, binding.parameter))
+            .is_none();
+        let force = new || binding.is_changed() || modulator.is_added();
+        modulate(
+            target,
+            binding.parameter,
+            Some((modulator.id(), binding.mapping)),
+            force,
+        );
+    }
+}
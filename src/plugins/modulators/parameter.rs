@@ -0,0 +1,275 @@
+use std::{fmt::Debug, sync::Arc};
+
+use bevy::prelude::*;
+use kira::Tween;
+
+use super::{KiraModulatedParameter, KiraModulatorBinding, KiraTweener};
+use crate::util::Removed;
+
+// Reads the gameplay value of a binding, `None` while it isn't available.
+type ParameterSource = Arc<dyn Fn(&World) -> Option<f32> + Send + Sync>;
+
+/// Drives an audio parameter from a gameplay value, such as the RPM of a vehicle or the health of
+/// the player. Every frame the value is read from the world, mapped through `curve` and tweened
+/// onto the parameter with `tween`.
+///
+/// The binding works through a [`KiraTweener`] and a [`KiraModulatorBinding`] that are inserted on
+/// the binding's entity and removed with it, so spawn one entity per binding. The parameter is set
+/// on the audio thread, no system has to watch the gameplay value for changes.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_mod_kira::{KiraModulatedParameter, KiraParameterBinding, KiraParameterCurve};
+/// #[derive(Component)]
+/// struct Engine {
+///     rpm: f32,
+/// }
+///
+/// fn setup_sys(mut commands: Commands) {
+///     let car = commands.spawn(Engine { rpm: 800.0 }).id();
+///     commands.spawn(KiraParameterBinding::from_component(
+///         car,
+///         |engine: &Engine| engine.rpm,
+///         car,
+///         KiraModulatedParameter::PlaybackRate,
+///         KiraParameterCurve::linear((800.0, 6000.0), (0.8, 2.0)),
+///     ));
+/// }
+/// ```
+#[derive(Component, Clone)]
+pub struct KiraParameterBinding {
+    source: ParameterSource,
+    pub target: Entity,
+    pub parameter: KiraModulatedParameter,
+    pub curve: KiraParameterCurve,
+    /// The tween moving the parameter to each new value, which smooths out the steps between
+    /// frames.
+    pub tween: Tween,
+}
+
+impl KiraParameterBinding {
+    /// Reads the gameplay value with a function of the whole world.
+    pub fn from_world(
+        source: fn(&World) -> f32,
+        target: Entity,
+        parameter: KiraModulatedParameter,
+        curve: KiraParameterCurve,
+    ) -> Self {
+        Self::new(
+            Arc::new(move |world| Some(source(world))),
+            target,
+            parameter,
+            curve,
+        )
+    }
+
+    /// Reads the gameplay value from the `C` component of the `source` entity. The parameter keeps
+    /// its last value while the entity doesn't have the component.
+    pub fn from_component<C: Component>(
+        source: Entity,
+        field: impl Fn(&C) -> f32 + Send + Sync + 'static,
+        target: Entity,
+        parameter: KiraModulatedParameter,
+        curve: KiraParameterCurve,
+    ) -> Self {
+        Self::new(
+            Arc::new(move |world| world.get::<C>(source).map(&field)),
+            target,
+            parameter,
+            curve,
+        )
+    }
+
+    fn new(
+        source: ParameterSource,
+        target: Entity,
+        parameter: KiraModulatedParameter,
+        curve: KiraParameterCurve,
+    ) -> Self {
+        Self {
+            source,
+            target,
+            parameter,
+            curve,
+            tween: Tween::default(),
+        }
+    }
+
+    pub fn with_tween(mut self, tween: Tween) -> Self {
+        self.tween = tween;
+        self
+    }
+}
+
+impl Debug for KiraParameterBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KiraParameterBinding")
+            .field("target", &self.target)
+            .field("parameter", &self.parameter)
+            .field("curve", &self.curve)
+            .field("tween", &self.tween)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Maps a gameplay value to a parameter value, interpolating linearly between points. Values
+/// outside of the points are clamped to the first or last point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KiraParameterCurve {
+    // The gameplay values and the parameter values they map to, sorted by gameplay value.
+    points: Vec<(f32, f64)>,
+}
+
+impl KiraParameterCurve {
+    pub fn new(points: impl IntoIterator<Item = (f32, f64)>) -> Self {
+        let mut points: Vec<_> = points.into_iter().collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { points }
+    }
+
+    /// A straight line from `input.0 => output.0` to `input.1 => output.1`.
+    pub fn linear(input: (f32, f32), output: (f64, f64)) -> Self {
+        Self::new([(input.0, output.0), (input.1, output.1)])
+    }
+
+    pub fn points(&self) -> &[(f32, f64)] {
+        &self.points
+    }
+
+    /// The parameter value for a gameplay value, `0.0` for a curve without points.
+    pub fn sample(&self, input: f32) -> f64 {
+        let Some(first) = self.points.first() else {
+            return 0.0;
+        };
+        let next = self.points.partition_point(|(x, _)| *x <= input);
+        if next == 0 {
+            return first.1;
+        }
+        let (x0, y0) = self.points[next - 1];
+        let Some(&(x1, y1)) = self.points.get(next) else {
+            return y0;
+        };
+        let amount = ((input - x0) / (x1 - x0)) as f64;
+        y0 + (y1 - y0) * amount
+    }
+
+    // The lowest and highest parameter values of the curve, `0.0` for both without points.
+    fn output_range(&self) -> (f64, f64) {
+        let first = self.points.first().map_or(0.0, |(_, y)| *y);
+        self.points
+            .iter()
+            .fold((first, first), |(min, max), (_, y)| {
+                (min.min(*y), max.max(*y))
+            })
+    }
+}
+
+type BindingOutputs = (
+    Option<&'static mut KiraTweener>,
+    Option<&'static mut KiraModulatorBinding>,
+);
+
+// The sources are read from the whole world first, then the outputs are written with a query.
+pub(super) fn parameter_bindings_sys(
+    mut commands: Commands,
+    bindings: Query<(Entity, &KiraParameterBinding)>,
+    mut world_and_outputs: ParamSet<(&World, Query<BindingOutputs>)>,
+    mut inputs: Local<Vec<(Entity, f32)>>,
+) {
+    let world = world_and_outputs.p0();
+    inputs.extend(
+        bindings
+            .iter()
+            .filter_map(|(eid, binding)| Some((eid, (binding.source)(world)?))),
+    );
+    let mut outputs = world_and_outputs.p1();
+    for (eid, input) in inputs.drain(..) {
+        let (Ok((_, binding)), Ok((tweener, modulator_binding))) =
+            (bindings.get(eid), outputs.get_mut(eid))
+        else {
+            continue;
+        };
+        let value = KiraTweener::new(binding.curve.sample(input)).with_tween(binding.tween);
+        // The tweener holds the parameter's value, so the modulator binding passes it through.
+        let (min, max) = binding.curve.output_range();
+        let range = if min < max {
+            (min, max)
+        } else {
+            (min, min + 1.0)
+        };
+        let modulation = KiraModulatorBinding::new(eid, binding.target, binding.parameter)
+            .with_mapping(range, range);
+        // Only write what differs so that the change detection of the modulators stays quiet.
+        match tweener {
+            Some(mut tweener) => {
+                tweener.set_if_neq(value);
+            }
+            None => {
+                commands.entity(eid).insert(value);
+            }
+        }
+        match modulator_binding {
+            Some(mut modulator_binding) => {
+                modulator_binding.set_if_neq(modulation);
+            }
+            None => {
+                commands.entity(eid).insert(modulation);
+            }
+        }
+    }
+}
+
+pub(super) fn remove_parameter_bindings_sys(
+    mut commands: Commands,
+    mut removed: Removed<KiraParameterBinding>,
+) {
+    for eid in removed.read() {
+        commands
+            .entity(eid)
+            .remove::<(KiraTweener, KiraModulatorBinding)>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_interpolates_and_clamps() {
+        let curve = KiraParameterCurve::new([(10.0, 2.0), (0.0, 0.0), (20.0, 1.0)]);
+        assert_eq!(curve.sample(-5.0), 0.0);
+        assert_eq!(curve.sample(5.0), 1.0);
+        assert_eq!(curve.sample(10.0), 2.0);
+        assert_eq!(curve.sample(15.0), 1.5);
+        assert_eq!(curve.sample(25.0), 1.0);
+        assert_eq!(KiraParameterCurve::new([]).sample(1.0), 0.0);
+        assert_eq!(KiraParameterCurve::new([(1.0, 3.0)]).sample(0.0), 3.0);
+    }
+
+    #[derive(Component)]
+    struct Speed(f32);
+
+    #[test]
+    fn bindings_write_the_sampled_value_to_their_tweener() {
+        let mut app = App::new();
+        app.add_systems(Update, parameter_bindings_sys);
+        let car = app.world_mut().spawn(Speed(5.0)).id();
+        let binding = app
+            .world_mut()
+            .spawn(KiraParameterBinding::from_component(
+                car,
+                |speed: &Speed| speed.0,
+                car,
+                KiraModulatedParameter::PlaybackRate,
+                KiraParameterCurve::linear((0.0, 10.0), (1.0, 2.0)),
+            ))
+            .id();
+        app.update();
+        let value = |app: &App| app.world().get::<KiraTweener>(binding).map(|t| t.value);
+        assert_eq!(value(&app), Some(1.5));
+        app.world_mut().get_mut::<Speed>(car).unwrap().0 = 10.0;
+        app.update();
+        assert_eq!(value(&app), Some(2.0));
+        assert!(app.world().get::<KiraModulatorBinding>(binding).is_some());
+    }
+}
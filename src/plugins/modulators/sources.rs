@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use kira::{
+    Tween,
+    modulator::{
+        ModulatorId,
+        lfo::{LfoBuilder, LfoHandle, Waveform},
+        tweener::{TweenerBuilder, TweenerHandle},
+    },
+};
+
+use crate::KiraContext;
+
+/// Creates a kira LFO, a modulator oscillating between `offset - amplitude` and
+/// `offset + amplitude`. Link it to audio parameters with a [`KiraModulatorBinding`]. Changes to
+/// the component are applied to the LFO.
+///
+/// [`KiraModulatorBinding`]: crate::KiraModulatorBinding
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KiraLfo {
+    pub waveform: Waveform,
+    /// The number of oscillations per second.
+    pub frequency: f64,
+    pub amplitude: f64,
+    pub offset: f64,
+}
+
+impl Default for KiraLfo {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Sine,
+            frequency: 2.0,
+            amplitude: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+/// Creates a kira tweener, a modulator moving smoothly to `value` with `tween` whenever the value
+/// changes. Link it to audio parameters with a [`KiraModulatorBinding`].
+///
+/// [`KiraModulatorBinding`]: crate::KiraModulatorBinding
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct KiraTweener {
+    pub value: f64,
+    pub tween: Tween,
+}
+
+impl KiraTweener {
+    pub fn new(value: f64) -> Self {
+        Self {
+            value,
+            tween: Tween::default(),
+        }
+    }
+
+    pub fn with_tween(mut self, tween: Tween) -> Self {
+        self.tween = tween;
+        self
+    }
+}
+
+// The kira handle of the modulator created for a `KiraLfo` or `KiraTweener`.
+#[derive(Component)]
+pub(crate) enum ModulatorHandle {
+    Lfo(LfoHandle),
+    Tweener(TweenerHandle),
+}
+
+impl ModulatorHandle {
+    pub(crate) fn id(&self) -> ModulatorId {
+        match self {
+            ModulatorHandle::Lfo(handle) => handle.id(),
+            ModulatorHandle::Tweener(handle) => handle.id(),
+        }
+    }
+}
+
+type NewModulator = (
+    Or<(With<KiraLfo>, With<KiraTweener>)>,
+    Without<ModulatorHandle>,
+);
+
+pub(super) fn add_modulators_sys(
+    mut commands: Commands,
+    mut kira: NonSendMut<KiraContext>,
+    query: Query<(Entity, Option<&KiraLfo>, Option<&KiraTweener>), NewModulator>,
+) {
+    for (eid, lfo, tweener) in query.iter() {
+        let handle = match (lfo, tweener) {
+            (Some(lfo), _) => kira
+                .add_modulator(
+                    LfoBuilder::new()
+                        .waveform(lfo.waveform)
+                        .frequency(lfo.frequency)
+                        .amplitude(lfo.amplitude)
+                        .offset(lfo.offset),
+                )
+                .map(ModulatorHandle::Lfo),
+            (None, Some(tweener)) => kira
+                .add_modulator(TweenerBuilder {
+                    initial_value: tweener.value,
+                })
+                .map(ModulatorHandle::Tweener),
+            (None, None) => continue,
+        };
+        match handle {
+            Ok(handle) => {
+                commands.entity(eid).insert(handle);
+            }
+            Err(e) => error!("Error adding modulator for entity {:?}: {}", eid, e),
+        }
+    }
+}
+
+type SyncedModulator = (
+    Option<Ref<'static, KiraLfo>>,
+    Option<Ref<'static, KiraTweener>>,
+    &'static mut ModulatorHandle,
+);
+
+type ChangedModulator = Or<(Changed<KiraLfo>, Changed<KiraTweener>)>;
+
+pub(super) fn sync_modulators_sys(mut query: Query<SyncedModulator, ChangedModulator>) {
+    for (lfo, tweener, mut handle) in query.iter_mut() {
+        match (handle.as_mut(), lfo, tweener) {
+            (ModulatorHandle::Lfo(handle), Some(lfo), _) if lfo.is_changed() => {
+                handle.set_waveform(lfo.waveform);
+                handle.set_frequency(lfo.frequency, Tween::default());
+                handle.set_amplitude(lfo.amplitude, Tween::default());
+                handle.set_offset(lfo.offset, Tween::default());
+            }
+            (ModulatorHandle::Tweener(handle), _, Some(tweener)) if tweener.is_changed() => {
+                handle.set(tweener.value, tweener.tween);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Modulators whose component was removed.
+type RemovedModulator = (
+    With<ModulatorHandle>,
+    Without<KiraLfo>,
+    Without<KiraTweener>,
+);
+
+pub(super) fn remove_modulators_sys(
+    mut commands: Commands,
+    query: Query<Entity, RemovedModulator>,
+) {
+    for eid in query.iter() {
+        commands.entity(eid).remove::<ModulatorHandle>();
+    }
+}
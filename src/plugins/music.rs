@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+mod graph;
+mod layers;
+mod player;
+pub use graph::*;
+pub use layers::*;
+pub use player::*;
+
+use super::events::do_play_sys;
+
+pub(crate) struct KiraMusicPlugin;
+
+impl Plugin for KiraMusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<KiraMusicGraph>()
+            .register_asset_loader(KiraMusicGraphLoader)
+            .add_event::<KiraMusicSegmentStarted>()
+            .add_systems(
+                Update,
+                (
+                    music_player_sys,
+                    music_graph_sys,
+                    (music_layers_sys, music_layers_intensity_sys).chain(),
+                    remove_music_layers_sys,
+                )
+                    .after(do_play_sys),
+            );
+    }
+}
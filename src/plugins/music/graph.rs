@@ -0,0 +1,493 @@
+use std::time::Duration;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use kira::{
+    Tween,
+    clock::ClockTime,
+    sound::static_sound::{StaticSoundData, StaticSoundHandle},
+};
+use serde::Deserialize;
+
+use crate::{
+    KiraContext, KiraStaticSoundAsset, KiraTrackHandle,
+    plugins::clock::{KiraClockHandle, KiraTempo},
+    sound::static_sounds::KiraError,
+};
+
+/// A piece of music in a [`KiraMusicGraph`].
+#[derive(Debug, Clone)]
+pub struct KiraMusicSegment {
+    pub name: String,
+    pub sound: Handle<KiraStaticSoundAsset>,
+    /// The length of the segment in bars of the clock's [`KiraTempo`].
+    pub bars: u64,
+    /// A segment to continue with once this one ends. If `None` the segment loops until
+    /// a transition is requested.
+    pub next: Option<String>,
+}
+
+/// When a transition between two segments of a [`KiraMusicGraph`] happens.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum KiraTransitionPoint {
+    /// On the next tick of the clock.
+    Immediate,
+    /// On the next beat.
+    NextBeat,
+    /// On the next bar.
+    #[default]
+    NextBar,
+    /// When the current segment reaches its end (or the end of its current loop).
+    SegmentEnd,
+}
+
+/// A rule allowing a [`KiraMusicGraphPlayer`] to move from one segment to another.
+#[derive(Debug, Clone)]
+pub struct KiraMusicTransition {
+    /// The name of the segment the transition starts from, `"*"` matches any segment.
+    pub from: String,
+    /// The name of the segment to transition to.
+    pub to: String,
+    pub on: KiraTransitionPoint,
+    /// An optional sound played at the moment of the transition.
+    pub stinger: Option<Handle<KiraStaticSoundAsset>>,
+    /// How long the previous segment takes to fade out.
+    pub fade_out: Duration,
+}
+
+/// Describes how a piece of music is split in segments and the transitions that are allowed
+/// between them. Music graphs can be built in code or loaded from `.musicgraph.ron` files, for
+/// example:
+///
+/// ```ron
+/// (
+///     initial: "intro",
+///     segments: [
+///         (name: "intro", sound: "music/intro.ogg", bars: 2, next: Some("explore")),
+///         (name: "explore", sound: "music/explore.ogg", bars: 8),
+///         (name: "combat", sound: "music/combat.ogg", bars: 4),
+///     ],
+///     transitions: [
+///         (from: "*", to: "combat", on: NextBar, stinger: Some("music/stinger.ogg")),
+///         (from: "combat", to: "explore", on: SegmentEnd, fade_out: 2.0),
+///     ],
+/// )
+/// ```
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct KiraMusicGraph {
+    /// The segment that plays first.
+    pub initial: String,
+    pub segments: Vec<KiraMusicSegment>,
+    pub transitions: Vec<KiraMusicTransition>,
+}
+
+impl KiraMusicGraph {
+    /// Returns the segment with the given name.
+    pub fn segment(&self, name: &str) -> Option<&KiraMusicSegment> {
+        self.segments.iter().find(|segment| segment.name == name)
+    }
+
+    /// Returns the transition rule to use when moving from `from` to `to`, rules naming the `from`
+    /// segment explicitly take priority over `"*"` rules.
+    pub fn transition(&self, from: &str, to: &str) -> Option<&KiraMusicTransition> {
+        let mut rules = self.transitions.iter().filter(|rule| rule.to == to);
+        rules
+            .clone()
+            .find(|rule| rule.from == from)
+            .or_else(|| rules.find(|rule| rule.from == "*"))
+    }
+}
+
+#[derive(Deserialize)]
+struct MusicGraphDescription {
+    initial: String,
+    segments: Vec<SegmentDescription>,
+    #[serde(default)]
+    transitions: Vec<TransitionDescription>,
+}
+
+#[derive(Deserialize)]
+struct SegmentDescription {
+    name: String,
+    sound: String,
+    bars: u64,
+    #[serde(default)]
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TransitionDescription {
+    from: String,
+    to: String,
+    #[serde(default)]
+    on: KiraTransitionPoint,
+    #[serde(default)]
+    stinger: Option<String>,
+    // In seconds.
+    #[serde(default)]
+    fade_out: Option<f64>,
+}
+
+pub struct KiraMusicGraphLoader;
+
+impl AssetLoader for KiraMusicGraphLoader {
+    type Asset = KiraMusicGraph;
+    type Settings = ();
+    type Error = KiraError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, KiraError> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+        let description: MusicGraphDescription = ron::de::from_bytes(&bytes)?;
+        let segments = description
+            .segments
+            .into_iter()
+            .map(|segment| KiraMusicSegment {
+                name: segment.name,
+                sound: load_context.load(segment.sound),
+                bars: segment.bars,
+                next: segment.next,
+            })
+            .collect();
+        let transitions = description
+            .transitions
+            .into_iter()
+            .map(|transition| KiraMusicTransition {
+                from: transition.from,
+                to: transition.to,
+                on: transition.on,
+                stinger: transition.stinger.map(|path| load_context.load(path)),
+                fade_out: transition
+                    .fade_out
+                    .map(Duration::from_secs_f64)
+                    .unwrap_or(Tween::default().duration),
+            })
+            .collect();
+        let graph = KiraMusicGraph {
+            initial: description.initial,
+            segments,
+            transitions,
+        };
+        validate(&graph)?;
+        Ok(graph)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["musicgraph.ron"]
+    }
+}
+
+fn validate(graph: &KiraMusicGraph) -> Result<(), KiraError> {
+    let names = std::iter::once(&graph.initial)
+        .chain(graph.segments.iter().filter_map(|s| s.next.as_ref()))
+        .chain(graph.transitions.iter().map(|t| &t.to))
+        .chain(
+            graph
+                .transitions
+                .iter()
+                .map(|t| &t.from)
+                .filter(|f| *f != "*"),
+        );
+    for name in names {
+        if graph.segment(name).is_none() {
+            return Err(KiraError::InvalidMusicGraph(format!(
+                "unknown segment \"{name}\""
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Written when a [`KiraMusicGraphPlayer`] starts playing a segment.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct KiraMusicSegmentStarted {
+    pub player: Entity,
+    pub segment: String,
+}
+
+struct PlayingSegment {
+    name: String,
+    // The clock tick the segment starts on.
+    start: u64,
+    handle: StaticSoundHandle,
+    // Whether the segment was scheduled as the `next` of the previous one rather than requested.
+    automatic: bool,
+}
+
+/// Plays a [`KiraMusicGraph`] in time with a clock entity.
+///
+/// The initial segment starts on the next bar of the clock, tempo and time signature are taken
+/// from the clock's [`KiraTempo`]. Call [`KiraMusicGraphPlayer::transition_to`] to move to another
+/// segment following the graph's transition rules. Transitions are scheduled on the clock ahead of
+/// time so the next segment (and the optional stinger) start exactly on the transition point.
+/// Segments with a `next` segment schedule it as soon as they start so the music continues
+/// seamlessly, a transition requested in the meantime replaces it.
+///
+/// A transition requested while another requested one is waiting for its transition point is
+/// applied once the first one has happened. A segment that fails to play is logged and no other
+/// segment is tried until the next bar, a requested transition that fails is dropped.
+#[derive(Component)]
+pub struct KiraMusicGraphPlayer {
+    pub graph: Handle<KiraMusicGraph>,
+    /// The entity with the clock (and optionally the [`KiraTempo`]) that drives the music.
+    pub clock: Entity,
+    /// An optional entity with a [`KiraTrackHandle`] to play the music on.
+    pub track_entity: Option<Entity>,
+    requested: Option<String>,
+    current: Option<PlayingSegment>,
+    pending: Option<PlayingSegment>,
+    // The tick before which no segment is played after one failed to play.
+    retry_at: Option<u64>,
+}
+
+impl KiraMusicGraphPlayer {
+    pub fn new(graph: Handle<KiraMusicGraph>, clock: Entity) -> Self {
+        Self {
+            graph,
+            clock,
+            track_entity: None,
+            requested: None,
+            current: None,
+            pending: None,
+            retry_at: None,
+        }
+    }
+
+    pub fn with_track(mut self, track_entity: Entity) -> Self {
+        self.track_entity = Some(track_entity);
+        self
+    }
+
+    /// Requests a transition to the segment with the given name.
+    pub fn transition_to(&mut self, segment: impl Into<String>) {
+        self.requested = Some(segment.into());
+    }
+
+    /// The name of the segment that is currently playing.
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_ref().map(|segment| segment.name.as_str())
+    }
+
+    /// The name of the segment that is scheduled to play next.
+    pub fn pending(&self) -> Option<&str> {
+        self.pending.as_ref().map(|segment| segment.name.as_str())
+    }
+
+    // The segments if they play on the main track, which can't be paused as a whole.
+    pub(crate) fn main_track_sounds(&mut self) -> impl Iterator<Item = &mut StaticSoundHandle> {
+        let main_track = self.track_entity.is_none();
+        self.current
+            .iter_mut()
+            .chain(self.pending.iter_mut())
+            .filter(move |_| main_track)
+            .map(|segment| &mut segment.handle)
+    }
+}
+
+pub(super) fn music_graph_sys(
+    mut kira: NonSendMut<KiraContext>,
+    graphs: Res<Assets<KiraMusicGraph>>,
+    assets: Res<Assets<KiraStaticSoundAsset>>,
+    mut players: Query<(Entity, &mut KiraMusicGraphPlayer)>,
+    mut tracks: Query<&mut KiraTrackHandle>,
+    clocks: Query<(&KiraClockHandle, Option<&KiraTempo>)>,
+    mut ev_started: EventWriter<KiraMusicSegmentStarted>,
+) {
+    for (eid, mut player) in players.iter_mut() {
+        let Some(graph) = graphs.get(&player.graph) else {
+            continue;
+        };
+        let Ok((clock, tempo)) = clocks.get(player.clock) else {
+            continue;
+        };
+        let clock = &clock.0;
+        if !clock.ticking() {
+            continue;
+        }
+        let tempo = tempo.copied().unwrap_or_default();
+        let now = clock.time().ticks;
+        let player = player.as_mut();
+
+        if player.pending.as_ref().is_some_and(|p| p.start <= now) {
+            player.current = player.pending.take();
+            if let Some(current) = &player.current {
+                ev_started.write(KiraMusicSegmentStarted {
+                    player: eid,
+                    segment: current.name.clone(),
+                });
+            }
+        }
+        if player
+            .pending
+            .as_ref()
+            .is_some_and(|pending| !pending.automatic || player.requested.is_none())
+        {
+            continue;
+        }
+        if player.retry_at.is_some_and(|tick| now < tick) {
+            continue;
+        }
+        let retry_at = (now / tempo.ticks_per_bar() + 1) * tempo.ticks_per_bar();
+
+        let mut track = match player.track_entity {
+            Some(track_entity) => match tracks.get_mut(track_entity) {
+                Ok(track) => Some(track),
+                Err(e) => {
+                    error!("Music graph player {:?} has no track: {}", eid, e);
+                    continue;
+                }
+            },
+            None => None,
+        };
+        // Returns `Ok(None)` while the segment's sound is loading.
+        let mut play = |segment: &KiraMusicSegment, start: u64, automatic: bool| {
+            let Some(sound) = assets.get(&segment.sound) else {
+                return Ok(None);
+            };
+            let mut sound: StaticSoundData = sound.sound.0.clone();
+            sound.settings.start_time = ClockTime::from_ticks_u64(clock, start).into();
+            if segment.next.is_none() {
+                sound.settings.loop_region = Some((..).into());
+            }
+            match kira.play_static(sound, track.as_deref_mut()) {
+                Ok(handle) => Ok(Some(PlayingSegment {
+                    name: segment.name.clone(),
+                    start,
+                    handle,
+                    automatic,
+                })),
+                Err(e) => {
+                    error!("Error playing music segment {}: {}", segment.name, e);
+                    Err(())
+                }
+            }
+        };
+
+        let Some(current) = &player.current else {
+            let Some(initial) = graph.segment(&graph.initial) else {
+                continue;
+            };
+            match play(initial, retry_at, false) {
+                Ok(pending) => player.pending = pending,
+                Err(()) => player.retry_at = Some(retry_at),
+            }
+            continue;
+        };
+        let Some(segment) = graph.segment(&current.name) else {
+            continue;
+        };
+        let length = segment.bars * tempo.ticks_per_bar();
+
+        if let Some(target) = player.requested.clone() {
+            let Some(rule) = graph.transition(&current.name, &target) else {
+                warn!(
+                    "Music graph has no transition from \"{}\" to \"{}\".",
+                    current.name, target
+                );
+                player.requested = None;
+                continue;
+            };
+            let Some(next) = graph.segment(&target) else {
+                player.requested = None;
+                continue;
+            };
+            let at = transition_point(rule.on, &tempo, now, current.start, length);
+            let pending = match play(next, at, false) {
+                Ok(Some(pending)) => pending,
+                Ok(None) => continue,
+                Err(()) => {
+                    player.requested = None;
+                    continue;
+                }
+            };
+            // The requested segment replaces the `next` segment that was scheduled automatically.
+            if let Some(mut automatic) = player.pending.take() {
+                automatic.handle.stop(Tween::default());
+            }
+            if let Some(stinger) = rule.stinger.as_ref().and_then(|s| assets.get(s)) {
+                let mut sound = stinger.sound.0.clone();
+                sound.settings.start_time = ClockTime::from_ticks_u64(clock, at).into();
+                if let Err(e) = kira.play_static(sound, track.as_deref_mut()) {
+                    error!("Error playing music stinger: {}", e);
+                }
+            }
+            if let Some(current) = player.current.as_mut() {
+                current.handle.stop(Tween {
+                    start_time: ClockTime::from_ticks_u64(clock, at).into(),
+                    duration: rule.fade_out,
+                    ..default()
+                });
+            }
+            player.pending = Some(pending);
+            player.requested = None;
+        } else if let Some(next) = segment.next.as_ref().and_then(|n| graph.segment(n)) {
+            // The current segment doesn't loop, it will stop by itself once it reaches its end.
+            let at = (current.start + length).max(now + 1);
+            match play(next, at, true) {
+                Ok(pending) => player.pending = pending,
+                Err(()) => player.retry_at = Some(retry_at),
+            }
+        }
+    }
+}
+
+fn transition_point(
+    on: KiraTransitionPoint,
+    tempo: &KiraTempo,
+    now: u64,
+    segment_start: u64,
+    segment_length: u64,
+) -> u64 {
+    let next_multiple = |every: u64, offset: u64| {
+        let every = every.max(1);
+        let elapsed = now.saturating_sub(offset);
+        offset + (elapsed / every + 1) * every
+    };
+    match on {
+        KiraTransitionPoint::Immediate => now + 1,
+        KiraTransitionPoint::NextBeat => next_multiple(tempo.ticks_per_beat(), 0),
+        KiraTransitionPoint::NextBar => next_multiple(tempo.ticks_per_bar(), 0),
+        KiraTransitionPoint::SegmentEnd => next_multiple(segment_length, segment_start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transition_points_follow_the_tempo() {
+        // 4/4 with two ticks per beat, so a bar is 8 ticks.
+        let tempo = KiraTempo::new(120.0, 4, 2);
+        let at = |on| transition_point(on, &tempo, 13, 4, 16);
+        assert_eq!(at(KiraTransitionPoint::Immediate), 14);
+        assert_eq!(at(KiraTransitionPoint::NextBeat), 14);
+        assert_eq!(at(KiraTransitionPoint::NextBar), 16);
+        assert_eq!(at(KiraTransitionPoint::SegmentEnd), 20);
+    }
+
+    #[test]
+    fn transition_points_are_always_in_the_future() {
+        let tempo = KiraTempo::new(120.0, 4, 1);
+        assert_eq!(
+            transition_point(KiraTransitionPoint::NextBar, &tempo, 8, 0, 8),
+            12
+        );
+        assert_eq!(
+            transition_point(KiraTransitionPoint::SegmentEnd, &tempo, 8, 0, 8),
+            16
+        );
+        // A segment that hasn't started yet ends one length after its start.
+        assert_eq!(
+            transition_point(KiraTransitionPoint::SegmentEnd, &tempo, 2, 4, 8),
+            12
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::Error;
+use bevy::prelude::*;
+use kira::{
+    Tween,
+    sound::{PlaybackState, static_sound::StaticSoundHandle},
+    track::TrackBuilder,
+};
+
+use crate::{
+    KiraContext, KiraStaticSoundAsset, KiraTrackHandle, KiraTrackVolume,
+    plugins::clock::{KiraClockHandle, KiraStartTime},
+    util::{Removed, amplitude_to_decibels},
+};
+
+/// One stem of a [`KiraMusicLayers`].
+#[derive(Debug, Clone)]
+pub struct KiraMusicLayer {
+    /// A name to look the layer up with, for example "drums".
+    pub name: String,
+    /// The stem to play.
+    pub sound: Handle<KiraStaticSoundAsset>,
+    /// The volume of the layer as a linear amplitude from `0.0` (silent) to `1.0`.
+    pub intensity: f32,
+}
+
+impl KiraMusicLayer {
+    pub fn new(
+        name: impl Into<String>,
+        sound: Handle<KiraStaticSoundAsset>,
+        intensity: f32,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            sound,
+            intensity,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LayersCommand {
+    Play,
+    Stop(Tween),
+}
+
+/// Plays several synchronized stems of a piece of music, each on its own sub-track, so the mix can
+/// adapt to the game by changing the intensity of each layer.
+///
+/// Once every stem has loaded they are all started on the same tick of the `clock` entity so the
+/// layers never drift apart. Each layer gets a child entity holding the [`KiraTrackHandle`] of its
+/// sub-track (see [`KiraMusicLayers::layer_track`]) which is added as a child of the track of
+/// `track_entity`, or of the main track. Changing a layer's `intensity` tweens its level of the
+/// track's [`KiraTrackVolume`] with `tween`. Removing the component stops the stems and despawns
+/// their tracks.
+#[derive(Component)]
+pub struct KiraMusicLayers {
+    /// The stems to play.
+    pub layers: Vec<KiraMusicLayer>,
+    /// The entity with the clock used to start the stems together.
+    pub clock: Entity,
+    /// Whether the stems loop.
+    pub looping: bool,
+    /// The tween used when the intensity of a layer changes.
+    pub tween: Tween,
+    /// An optional entity with a [`KiraTrackHandle`] that the layer tracks are routed into.
+    pub track_entity: Option<Entity>,
+    command: Option<LayersCommand>,
+    tracks: Vec<Entity>,
+    handles: Vec<StaticSoundHandle>,
+    // Stems that were stopped, and the tracks that were replaced while they still play on them.
+    stopping: Vec<StaticSoundHandle>,
+    old_tracks: Vec<Entity>,
+}
+
+// Marks the child entities holding the tracks of a `KiraMusicLayers`.
+#[derive(Component)]
+pub(super) struct LayerTrack;
+
+impl KiraMusicLayers {
+    /// Creates looping layers that start playing on the next tick of `clock`.
+    pub fn new(clock: Entity, layers: impl IntoIterator<Item = KiraMusicLayer>) -> Self {
+        Self {
+            layers: layers.into_iter().collect(),
+            clock,
+            looping: true,
+            tween: Tween::default(),
+            track_entity: None,
+            command: Some(LayersCommand::Play),
+            tracks: vec![],
+            handles: vec![],
+            stopping: vec![],
+            old_tracks: vec![],
+        }
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn with_tween(mut self, tween: Tween) -> Self {
+        self.tween = tween;
+        self
+    }
+
+    pub fn with_track(mut self, track_entity: Entity) -> Self {
+        self.track_entity = Some(track_entity);
+        self
+    }
+
+    /// Sets the intensity of the layer with the given name.
+    pub fn set_intensity(&mut self, name: &str, intensity: f32) {
+        if let Some(layer) = self.layers.iter_mut().find(|layer| layer.name == name) {
+            layer.intensity = intensity;
+        }
+    }
+
+    /// Returns the intensity of the layer with the given name.
+    pub fn intensity(&self, name: &str) -> Option<f32> {
+        self.layers
+            .iter()
+            .find(|layer| layer.name == name)
+            .map(|layer| layer.intensity)
+    }
+
+    /// The entity holding the [`KiraTrackHandle`] of the layer at `index`, once the layers have
+    /// started.
+    pub fn layer_track(&self, index: usize) -> Option<Entity> {
+        self.tracks.get(index).copied()
+    }
+
+    /// Restarts every stem from the beginning on the next tick of the clock.
+    pub fn play(&mut self) {
+        self.command = Some(LayersCommand::Play);
+    }
+
+    /// Fades out and stops every stem.
+    pub fn stop(&mut self, fade_out: Tween) {
+        self.command = Some(LayersCommand::Stop(fade_out));
+    }
+}
+
+pub(super) fn music_layers_sys(
+    mut commands: Commands,
+    mut kira: NonSendMut<KiraContext>,
+    assets: Res<Assets<KiraStaticSoundAsset>>,
+    mut query: Query<(Entity, &mut KiraMusicLayers)>,
+    mut tracks: Query<&mut KiraTrackHandle>,
+    clocks: Query<&KiraClockHandle>,
+) {
+    for (eid, mut layers) in query.iter_mut() {
+        despawn_old_tracks(&mut commands, layers.bypass_change_detection());
+        let Some(command) = layers.command else {
+            continue;
+        };
+        let layers = layers.as_mut();
+        if let LayersCommand::Stop(fade_out) = command {
+            for mut handle in layers.handles.drain(..) {
+                handle.stop(fade_out);
+                layers.stopping.push(handle);
+            }
+            layers.command = None;
+            continue;
+        }
+        // Wait until every stem is ready so they can all be started together.
+        let Some(sounds) = layers
+            .layers
+            .iter()
+            .map(|layer| assets.get(&layer.sound))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let Ok(clock) = clocks.get(layers.clock) else {
+            continue;
+        };
+        let start_time = KiraStartTime::NextTick(layers.clock).resolve(clock);
+        if layers.tracks.len() != layers.layers.len() {
+            let track_entity = layers.track_entity;
+            let handles = layers
+                .layers
+                .iter()
+                .map(|_| {
+                    let builder = TrackBuilder::new();
+                    match track_entity {
+                        Some(parent) => tracks
+                            .get_mut(parent)?
+                            .0
+                            .add_sub_track(builder)
+                            .map_err(|e| e.into()),
+                        None => kira.add_track(builder),
+                    }
+                })
+                .collect::<Result<Vec<_>, Error>>();
+            let handles = match handles {
+                Ok(handles) => handles,
+                Err(e) => {
+                    error!("Error adding tracks for music layers {:?}: {}", eid, e);
+                    layers.command = None;
+                    continue;
+                }
+            };
+            // The old stems keep playing on their tracks until the new stems start.
+            let old_tracks = std::mem::take(&mut layers.tracks);
+            layers.old_tracks.extend(old_tracks);
+            for (layer, handle) in layers.layers.iter().zip(handles) {
+                let volume =
+                    KiraTrackVolume::with_intensity(amplitude_to_decibels(layer.intensity));
+                let track = commands
+                    .spawn((KiraTrackHandle(handle), volume, LayerTrack))
+                    .id();
+                commands.entity(eid).add_child(track);
+                layers.tracks.push(track);
+            }
+        }
+        // The track entities are spawned with commands, if they were just created wait until the
+        // next frame for their handles to be available.
+        if tracks.iter_many(&layers.tracks).count() != layers.tracks.len() {
+            continue;
+        }
+        // The old stems stop on the tick the new ones start on so there is no gap between them.
+        for mut handle in layers.handles.drain(..) {
+            handle.stop(Tween {
+                start_time,
+                ..default()
+            });
+            layers.stopping.push(handle);
+        }
+        for (index, sound) in sounds.into_iter().enumerate() {
+            let mut sound = sound.sound.0.clone();
+            sound.settings.start_time = start_time;
+            if layers.looping {
+                sound.settings.loop_region = Some((..).into());
+            }
+            let track = layers.tracks[index];
+            let Ok(mut track) = tracks.get_mut(track) else {
+                continue;
+            };
+            match kira.play_static(sound, Some(&mut track)) {
+                Ok(handle) => layers.handles.push(handle),
+                Err(e) => error!("Error playing music layer: {}", e),
+            }
+        }
+        layers.command = None;
+    }
+}
+
+// Despawns the replaced tracks once the stems playing on them have stopped.
+fn despawn_old_tracks(commands: &mut Commands, layers: &mut KiraMusicLayers) {
+    layers
+        .stopping
+        .retain(|handle| handle.state() != PlaybackState::Stopped);
+    if layers.stopping.is_empty() {
+        for track in layers.old_tracks.drain(..) {
+            commands.entity(track).despawn();
+        }
+    }
+}
+
+// Despawning the layer tracks also stops the stems playing on them.
+pub(super) fn remove_music_layers_sys(
+    mut commands: Commands,
+    mut removed: Removed<KiraMusicLayers>,
+    children: Query<&Children>,
+    layer_tracks: Query<(), With<LayerTrack>>,
+) {
+    for eid in removed.read() {
+        for &child in children.get(eid).into_iter().flatten() {
+            if layer_tracks.contains(child) {
+                commands.entity(child).despawn();
+            }
+        }
+    }
+}
+
+pub(super) fn music_layers_intensity_sys(
+    query: Query<&KiraMusicLayers, Changed<KiraMusicLayers>>,
+    mut volumes: Query<&mut KiraTrackVolume>,
+) {
+    for layers in query.iter() {
+        for (layer, track) in layers.layers.iter().zip(layers.tracks.iter()) {
+            let Ok(mut volume) = volumes.get_mut(*track) else {
+                continue;
+            };
+            let intensity = amplitude_to_decibels(layer.intensity);
+            if volume.intensity != intensity {
+                volume.intensity = intensity;
+                volume.tween = layers.tween;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kira::Decibels;
+
+    use super::*;
+
+    #[test]
+    fn intensity_keeps_other_track_levels() {
+        let mut app = App::new();
+        app.add_systems(Update, music_layers_intensity_sys);
+        let track = app
+            .world_mut()
+            .spawn(KiraTrackVolume::with_intensity(amplitude_to_decibels(1.0)))
+            .id();
+        let mut layers = KiraMusicLayers::new(
+            Entity::PLACEHOLDER,
+            [KiraMusicLayer::new("drums", Handle::default(), 1.0)],
+        );
+        layers.tracks.push(track);
+        let layers = app.world_mut().spawn(layers).id();
+        app.update();
+        // A snapshot sets its own level of the layer's track.
+        app.world_mut()
+            .get_mut::<KiraTrackVolume>(track)
+            .unwrap()
+            .snapshot = Decibels(-6.0);
+        app.world_mut()
+            .get_mut::<KiraMusicLayers>(layers)
+            .unwrap()
+            .set_intensity("drums", 0.5);
+        app.update();
+        let volume = app.world().get::<KiraTrackVolume>(track).unwrap();
+        let intensity = amplitude_to_decibels(0.5);
+        assert_eq!(volume.intensity, intensity);
+        assert_eq!(volume.snapshot, Decibels(-6.0));
+        assert_eq!(volume.decibels(), Decibels(intensity.0 - 6.0));
+    }
+}
@@ -0,0 +1,382 @@
+use bevy::prelude::*;
+use kira::{
+    StartTime, Tween, Value,
+    sound::{
+        PlaybackState,
+        static_sound::{StaticSoundData, StaticSoundHandle},
+    },
+};
+
+use crate::{
+    KiraContext, KiraStaticSoundAsset, KiraTrackHandle,
+    plugins::clock::{KiraClockHandle, KiraStartTime},
+};
+
+/// How a [`KiraMusicPlayer`] continues once a song ends.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KiraRepeatMode {
+    /// Stop after the last song of the playlist, or once every song has played when shuffling.
+    Off,
+    /// Repeat the current song.
+    One,
+    /// Start over from the beginning of the playlist after the last song.
+    #[default]
+    All,
+}
+
+/// Makes a [`KiraMusicPlayer`] start its transitions on the next tick of `clock` that is
+/// a multiple of `every`, for example on the next bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KiraMusicSync {
+    /// The entity with the clock to synchronize to.
+    pub clock: Entity,
+    /// The number of ticks between possible transition points.
+    pub every: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MusicCommand {
+    Play(usize, Tween),
+    Stop(Tween),
+}
+
+/// Plays the songs of a playlist one after the other, crossfading between them.
+///
+/// Songs are played on the track of `track_entity` (or the main track). When a song is about to
+/// end the next one is started `crossfade.duration` early so the two overlap. Use
+/// [`KiraMusicPlayer::play`] to switch songs at any time, for example when the game state changes.
+/// When `sync` is set every transition is delayed until the next transition point of the clock so
+/// songs change on the beat. Songs that are still fading out are stopped along with the current song
+/// and paused by [`KiraAudioPause`].
+///
+/// [`KiraAudioPause`]: crate::KiraAudioPause
+#[derive(Component)]
+pub struct KiraMusicPlayer {
+    /// The songs that can be played.
+    pub playlist: Vec<Handle<KiraStaticSoundAsset>>,
+    /// Pick the next song at random rather than in playlist order.
+    pub shuffle: bool,
+    /// What to do when a song ends.
+    pub repeat: KiraRepeatMode,
+    /// The tween used when one song ends and the next one starts.
+    pub crossfade: Tween,
+    /// Optionally synchronizes transitions to a clock.
+    pub sync: Option<KiraMusicSync>,
+    /// An entity with a [`KiraTrackHandle`] to play the music on. If `None` the music is played
+    /// on the main track.
+    pub track_entity: Option<Entity>,
+    current: Option<usize>,
+    // The songs played since the shuffle last went through the whole playlist.
+    played: Vec<usize>,
+    command: Option<MusicCommand>,
+    handle: Option<StaticSoundHandle>,
+    // Songs that are fading out, kept until they stop so they can still be paused and stopped.
+    fading: Vec<StaticSoundHandle>,
+}
+
+impl KiraMusicPlayer {
+    /// Creates a player for the given playlist. Nothing plays until [`KiraMusicPlayer::play`] is
+    /// called.
+    pub fn new(playlist: impl IntoIterator<Item = Handle<KiraStaticSoundAsset>>) -> Self {
+        Self {
+            playlist: playlist.into_iter().collect(),
+            shuffle: false,
+            repeat: KiraRepeatMode::default(),
+            crossfade: Tween::default(),
+            sync: None,
+            track_entity: None,
+            current: None,
+            played: vec![],
+            command: None,
+            handle: None,
+            fading: vec![],
+        }
+    }
+
+    pub fn with_shuffle(mut self, shuffle: bool) -> Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: KiraRepeatMode) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn with_crossfade(mut self, crossfade: Tween) -> Self {
+        self.crossfade = crossfade;
+        self
+    }
+
+    pub fn with_sync(mut self, sync: KiraMusicSync) -> Self {
+        self.sync = Some(sync);
+        self
+    }
+
+    pub fn with_track(mut self, track_entity: Entity) -> Self {
+        self.track_entity = Some(track_entity);
+        self
+    }
+
+    /// Crossfades from the current song to the song at `index` in the playlist.
+    pub fn play(&mut self, index: usize, crossfade: Tween) {
+        self.command = Some(MusicCommand::Play(index, crossfade));
+    }
+
+    /// Crossfades to the song that would play after the current one, following the shuffle
+    /// setting. Does nothing if the playlist is empty.
+    pub fn next(&mut self, crossfade: Tween) {
+        if let Some(index) = self.pick_next(KiraRepeatMode::All) {
+            self.play(index, crossfade);
+        }
+    }
+
+    /// Fades out and stops the current song.
+    pub fn stop(&mut self, fade_out: Tween) {
+        self.command = Some(MusicCommand::Stop(fade_out));
+    }
+
+    /// The playlist index of the song that is currently playing.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// The handle of the song that is currently playing.
+    pub fn handle(&self) -> Option<&StaticSoundHandle> {
+        self.handle.as_ref()
+    }
+
+    // The song if it plays on the main track, which can't be paused as a whole.
+    pub(crate) fn main_track_sounds(&mut self) -> impl Iterator<Item = &mut StaticSoundHandle> {
+        let main_track = self.track_entity.is_none();
+        self.handle.iter_mut().filter(move |_| main_track)
+    }
+
+    // The songs fading out on the main track.
+    pub(crate) fn main_track_fading(&mut self) -> impl Iterator<Item = &mut StaticSoundHandle> {
+        let main_track = self.track_entity.is_none();
+        self.fading.iter_mut().filter(move |_| main_track)
+    }
+
+    fn pick_next(&self, repeat: KiraRepeatMode) -> Option<usize> {
+        let len = self.playlist.len();
+        if len == 0 {
+            return None;
+        }
+        let Some(current) = self.current else {
+            return Some(if self.shuffle {
+                fastrand::usize(..len)
+            } else {
+                0
+            });
+        };
+        match repeat {
+            KiraRepeatMode::One => Some(current),
+            _ if self.shuffle && len > 1 => {
+                let unplayed: Vec<usize> = (0..len)
+                    .filter(|index| !self.played.contains(index))
+                    .collect();
+                if !unplayed.is_empty() {
+                    return Some(unplayed[fastrand::usize(..unplayed.len())]);
+                }
+                if repeat == KiraRepeatMode::Off {
+                    return None;
+                }
+                // Start over, but never with the song that just played.
+                let next = fastrand::usize(..len - 1);
+                Some(if next >= current { next + 1 } else { next })
+            }
+            KiraRepeatMode::Off if current + 1 >= len => None,
+            _ => Some((current + 1) % len),
+        }
+    }
+}
+
+pub(super) fn music_player_sys(
+    mut kira: NonSendMut<KiraContext>,
+    assets: Res<Assets<KiraStaticSoundAsset>>,
+    mut players: Query<(Entity, &mut KiraMusicPlayer)>,
+    mut tracks: Query<&mut KiraTrackHandle>,
+    clocks: Query<&KiraClockHandle>,
+) {
+    for (eid, mut player) in players.iter_mut() {
+        player
+            .bypass_change_detection()
+            .fading
+            .retain(|handle| handle.state() != PlaybackState::Stopped);
+        if player.command.is_none() {
+            queue_next_song(&mut player, &assets);
+        }
+        let Some(command) = player.command else {
+            continue;
+        };
+        let start_time = match player.sync {
+            Some(sync) => match clocks.get(sync.clock) {
+                Ok(clock) => KiraStartTime::Quantized {
+                    clock: sync.clock,
+                    every: sync.every,
+                }
+                .resolve(clock),
+                Err(_) => {
+                    error!(
+                        "Music player {:?} is synced to entity {:?} which has no clock.",
+                        eid, sync.clock
+                    );
+                    StartTime::Immediate
+                }
+            },
+            None => StartTime::Immediate,
+        };
+        match command {
+            MusicCommand::Play(index, crossfade) => {
+                let Some(song) = player.playlist.get(index) else {
+                    error!("Music player {:?} has no song at index {}.", eid, index);
+                    player.command = None;
+                    continue;
+                };
+                // Keep the command around until the song has loaded.
+                let Some(song) = assets.get(song) else {
+                    continue;
+                };
+                let mut sound = song.sound.0.clone();
+                sound.settings.start_time = start_time;
+                sound.settings.fade_in_tween = Some(crossfade);
+                let track = match player.track_entity {
+                    Some(track_entity) => match tracks.get_mut(track_entity) {
+                        Ok(track) => Some(track),
+                        Err(e) => {
+                            error!("Music player {:?} could not find its track: {}", eid, e);
+                            player.command = None;
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                let handle = match kira.play_static(sound, track.map(|t| t.into_inner())) {
+                    Ok(handle) => handle,
+                    Err(e) => {
+                        error!("Error playing music: {}", e);
+                        player.command = None;
+                        continue;
+                    }
+                };
+                if let Some(mut previous) = player.handle.replace(handle) {
+                    previous.stop(Tween {
+                        start_time,
+                        ..crossfade
+                    });
+                    player.fading.push(previous);
+                }
+                // Playing a song again starts a new pass through the shuffled playlist.
+                if player.played.contains(&index) {
+                    player.played.clear();
+                }
+                player.played.push(index);
+                player.current = Some(index);
+            }
+            MusicCommand::Stop(fade_out) => {
+                let player = player.as_mut();
+                if let Some(previous) = player.handle.take() {
+                    player.fading.push(previous);
+                }
+                for handle in player.fading.iter_mut() {
+                    handle.stop(Tween {
+                        start_time,
+                        ..fade_out
+                    });
+                }
+                player.current = None;
+            }
+        }
+        player.command = None;
+    }
+}
+
+// Starts the crossfade to the next song once the current one is about to end.
+fn queue_next_song(player: &mut Mut<KiraMusicPlayer>, assets: &Assets<KiraStaticSoundAsset>) {
+    let (Some(current), Some(handle)) = (player.current, player.handle.as_ref()) else {
+        return;
+    };
+    let stopped = handle.state() == PlaybackState::Stopped;
+    let remaining = player
+        .playlist
+        .get(current)
+        .and_then(|song| assets.get(song))
+        .and_then(|song| remaining(&song.sound.0, handle.position()));
+    let ending =
+        remaining.is_some_and(|remaining| remaining <= player.crossfade.duration.as_secs_f64());
+    if !stopped && !ending {
+        return;
+    }
+    match player.pick_next(player.repeat) {
+        Some(next) => {
+            let crossfade = player.crossfade;
+            player.play(next, crossfade);
+        }
+        None if stopped => {
+            player.current = None;
+            player.handle = None;
+        }
+        None => {}
+    }
+}
+
+// How long a song has left to play in real time given its position, `None` if it loops or its
+// playback rate follows a modulator so it can only be told apart once it has stopped.
+fn remaining(song: &StaticSoundData, position: f64) -> Option<f64> {
+    let settings = &song.settings;
+    if settings.loop_region.is_some() {
+        return None;
+    }
+    let Value::Fixed(rate) = settings.playback_rate else {
+        return None;
+    };
+    let rate = rate.0.abs();
+    if rate <= 0.0 {
+        return None;
+    }
+    let left = if settings.reverse {
+        position
+    } else {
+        song.duration().as_secs_f64() - position
+    };
+    Some(left / rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use kira::{Frame, PlaybackRate, sound::static_sound::StaticSoundSettings};
+
+    use super::*;
+
+    #[test]
+    fn shuffle_with_repeat_off_ends_after_every_song() {
+        let mut player = KiraMusicPlayer::new((0..3).map(|_| Handle::default()))
+            .with_shuffle(true)
+            .with_repeat(KiraRepeatMode::Off);
+        for _ in 0..3 {
+            let next = player.pick_next(player.repeat).unwrap();
+            assert!(!player.played.contains(&next));
+            player.played.push(next);
+            player.current = Some(next);
+        }
+        assert_eq!(player.pick_next(player.repeat), None);
+    }
+
+    #[test]
+    fn remaining_follows_rate_and_direction() {
+        let song = StaticSoundData {
+            sample_rate: 10,
+            frames: Arc::new([Frame::ZERO; 100]),
+            settings: StaticSoundSettings::new().playback_rate(PlaybackRate(2.0)),
+            slice: None,
+        };
+        assert_eq!(remaining(&song, 4.0), Some(3.0));
+        let reversed = song.with_settings(StaticSoundSettings::new().reverse(true));
+        assert_eq!(remaining(&reversed, 4.0), Some(4.0));
+        let looping = reversed.loop_region(..);
+        assert_eq!(remaining(&looping, 4.0), None);
+    }
+}
@@ -0,0 +1,254 @@
+use std::{any::type_name, time::Duration};
+
+use bevy::{platform::collections::HashSet, prelude::*};
+use kira::{
+    Tween,
+    sound::{PlaybackState, static_sound::StaticSoundHandle},
+};
+
+use crate::{
+    KiraMusicGraphPlayer, KiraMusicPlayer, KiraPlayingSounds, KiraTrackHandle,
+    sound::sound_types::KiraPlayingSound,
+};
+
+/// Which audio [`KiraAudioPause`] pauses.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum KiraPauseScope {
+    /// Every track with a [`KiraTrackHandle`], every sound played on the main track through
+    /// a [`KiraPlaySoundEvent`] (dynamic sounds through [`DynamicSoundHandle::pause`]) and the music of [`KiraMusicPlayer`]s and [`KiraMusicGraphPlayer`]s
+    /// playing on the main track. [`KiraMusicLayers`] are paused through their tracks.
+    ///
+    /// [`KiraPlaySoundEvent`]: crate::KiraPlaySoundEvent
+    /// [`DynamicSoundHandle::pause`]: crate::DynamicSoundHandle::pause
+    /// [`KiraMusicPlayer`]: crate::KiraMusicPlayer
+    /// [`KiraMusicGraphPlayer`]: crate::KiraMusicGraphPlayer
+    /// [`KiraMusicLayers`]: crate::KiraMusicLayers
+    ///
+    /// Every [`KiraClock`] is paused as well, so sequencers and music synced to a clock stop
+    /// scheduling sounds.
+    ///
+    /// [`KiraClock`]: crate::KiraClock
+    #[default]
+    All,
+    /// Only the tracks of the given entities, for example the music and sfx buses so that menu
+    /// sounds keep playing. Clocks on the given entities are paused as well.
+    Tracks(Vec<Entity>),
+}
+
+/// Pauses and resumes audio for the whole game, for example while the game is paused or the window
+/// is out of focus.
+///
+/// Audio is paused as long as at least one reason to pause it is active, so the pause menu and
+/// a focus loss can overlap without resuming audio too early. Pausing and resuming fade the audio
+/// with `tween`.
+///
+/// Kira can't pause the main track itself: with [`KiraPauseScope::All`] the sounds that are
+/// playing on the main track are paused individually. Tracks and sounds created while audio is
+/// paused start paused, use [`KiraPauseScope::Tracks`] to keep UI sounds playing. Changing the
+/// scope while audio is paused resumes what is no longer in it.
+#[derive(Resource, Debug, Default)]
+pub struct KiraAudioPause {
+    pub scope: KiraPauseScope,
+    pub tween: Tween,
+    /// Pause audio while no window of the app has focus.
+    pub pause_on_focus_loss: bool,
+    reasons: HashSet<String>,
+    // Whether audio was paused and the scope it was paused in the last time the pause was applied.
+    applied: bool,
+    applied_scope: KiraPauseScope,
+}
+
+impl KiraAudioPause {
+    /// Pauses audio until every reason passed to `pause` has been passed to `resume`.
+    pub fn pause(&mut self, reason: impl Into<String>) {
+        self.reasons.insert(reason.into());
+    }
+
+    pub fn resume(&mut self, reason: &str) {
+        self.reasons.remove(reason);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+
+    // Whether the clock of the given entity should be paused.
+    pub(crate) fn pauses_clock(&self, clock: Entity) -> bool {
+        pauses(self.is_paused(), &self.scope, Some(clock))
+    }
+}
+
+// Whether the pause applies to the given track, `None` being the main track.
+fn pauses(paused: bool, scope: &KiraPauseScope, track: Option<Entity>) -> bool {
+    paused
+        && match (scope, track) {
+            (KiraPauseScope::All, _) => true,
+            (KiraPauseScope::Tracks(selected), Some(track)) => selected.contains(&track),
+            (KiraPauseScope::Tracks(_), None) => false,
+        }
+}
+
+/// Pauses audio while the app is in the given state, for example `GameState::Paused`. Add it
+/// alongside [`KiraPlugin`], see [`KiraAudioPause`].
+///
+/// [`KiraPlugin`]: crate::KiraPlugin
+pub struct KiraPauseOnState<S: States> {
+    pub state: S,
+}
+
+impl<S: States> KiraPauseOnState<S> {
+    pub fn new(state: S) -> Self {
+        Self { state }
+    }
+}
+
+impl<S: States> Plugin for KiraPauseOnState<S> {
+    fn build(&self, app: &mut App) {
+        let reason = format!("{}::{:?}", type_name::<S>(), self.state);
+        let resume_reason = reason.clone();
+        app.add_systems(
+            OnEnter(self.state.clone()),
+            move |mut pause: ResMut<KiraAudioPause>| pause.pause(reason.clone()),
+        )
+        .add_systems(
+            OnExit(self.state.clone()),
+            move |mut pause: ResMut<KiraAudioPause>| pause.resume(&resume_reason),
+        );
+    }
+}
+
+const FOCUS_REASON: &str = "focus";
+
+pub(crate) struct KiraPausePlugin;
+
+impl Plugin for KiraPausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KiraAudioPause>()
+            .add_systems(PostUpdate, (focus_pause_sys, apply_pause_sys).chain());
+    }
+}
+
+fn focus_pause_sys(mut pause: ResMut<KiraAudioPause>, windows: Query<&Window>) {
+    let unfocused = pause.pause_on_focus_loss
+        && !windows.is_empty()
+        && !windows.iter().any(|window| window.focused);
+    // Only touch the resource when the reason changes so its change detection stays quiet.
+    if unfocused != pause.reasons.contains(FOCUS_REASON) {
+        if unfocused {
+            pause.pause(FOCUS_REASON);
+        } else {
+            pause.resume(FOCUS_REASON);
+        }
+    }
+}
+
+fn apply_pause_sys(
+    mut pause: ResMut<KiraAudioPause>,
+    mut tracks: Query<(Entity, &mut KiraTrackHandle)>,
+    mut sounds: Query<&mut KiraPlayingSounds>,
+    mut music_players: Query<&mut KiraMusicPlayer>,
+    mut graph_players: Query<&mut KiraMusicGraphPlayer>,
+) {
+    let paused = pause.is_paused();
+    let changed = paused != pause.applied || pause.scope != pause.applied_scope;
+    // While paused audio that was just created has to be paused as well.
+    if !changed && !paused {
+        return;
+    }
+    let pause = pause.bypass_change_detection();
+    let (was_paused, scope) = (pause.applied, pause.scope.clone());
+    let was_scope = std::mem::replace(&mut pause.applied_scope, scope.clone());
+    pause.applied = paused;
+    // New audio is paused right away rather than faded out.
+    let instant = Tween {
+        duration: Duration::ZERO,
+        ..default()
+    };
+    let tween = if changed { pause.tween } else { instant };
+    for (eid, mut track) in tracks.iter_mut() {
+        // Tracks created since the last update were not paused yet.
+        let before = pauses(was_paused, &was_scope, Some(eid)) && !track.is_added();
+        let after = pauses(paused, &scope, Some(eid));
+        if before == after {
+            continue;
+        }
+        let tween = if track.is_added() {
+            instant
+        } else {
+            pause.tween
+        };
+        let track = &mut track.bypass_change_detection().0;
+        if after {
+            track.pause(tween);
+        } else {
+            track.resume(tween);
+        }
+    }
+    let before = pauses(was_paused, &was_scope, None);
+    let after = pauses(paused, &scope, None);
+    if !before && !after {
+        return;
+    }
+    for mut sounds in sounds.iter_mut() {
+        if !changed && !sounds.is_changed() {
+            continue;
+        }
+        for voice in sounds.bypass_change_detection().0.iter_mut() {
+            if voice.track.is_some() || voice.paused == after {
+                continue;
+            }
+            match &mut voice.sound {
+                KiraPlayingSound::Static(sound) if after => sound.pause(tween),
+                KiraPlayingSound::Static(sound) => sound.resume(tween),
+                KiraPlayingSound::Dynamic(sound) if after => sound.pause(tween),
+                KiraPlayingSound::Dynamic(sound) => sound.resume(tween),
+            }
+            voice.paused = after;
+        }
+    }
+    // The handles of the music players are private, so any of them that isn't paused while audio
+    // is paused is a new song and any that is paused was paused here.
+    let mut apply = |sound: &mut StaticSoundHandle| {
+        let state = sound.state();
+        if after && state == PlaybackState::Playing {
+            sound.pause(tween);
+        } else if !after && matches!(state, PlaybackState::Pausing | PlaybackState::Paused) {
+            sound.resume(tween);
+        }
+    };
+    for mut player in music_players.iter_mut() {
+        let player = player.bypass_change_detection();
+        player.main_track_sounds().for_each(&mut apply);
+        // Pausing a song that fades out cancels its fade, so it is stopped rather than resumed.
+        for sound in player.main_track_fading() {
+            let state = sound.state();
+            if after && !matches!(state, PlaybackState::Pausing | PlaybackState::Paused) {
+                sound.pause(tween);
+            } else if !after && matches!(state, PlaybackState::Pausing | PlaybackState::Paused) {
+                sound.stop(tween);
+            }
+        }
+    }
+    for mut player in graph_players.iter_mut() {
+        player
+            .bypass_change_detection()
+            .main_track_sounds()
+            .for_each(&mut apply);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_select_tracks_and_the_main_track() {
+        let (music, ui) = (Entity::from_raw(1), Entity::from_raw(2));
+        let tracks = KiraPauseScope::Tracks(vec![music]);
+        assert!(pauses(true, &tracks, Some(music)));
+        assert!(!pauses(true, &tracks, Some(ui)));
+        assert!(!pauses(true, &tracks, None));
+        assert!(pauses(true, &KiraPauseScope::All, None));
+        assert!(!pauses(false, &KiraPauseScope::All, Some(music)));
+    }
+}
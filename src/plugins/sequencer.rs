@@ -0,0 +1,281 @@
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use kira::clock::ClockTime;
+
+use crate::{
+    KiraPlaySoundEvent, KiraStaticSoundAsset,
+    plugins::{clock::KiraClockHandle, events::do_play_sys},
+    util::{amplitude_to_decibels, map_value},
+};
+
+/// A single step of a [`KiraSequencerTrack`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KiraStep {
+    /// Whether the step plays its sound.
+    pub active: bool,
+    /// The volume of the step as a linear amplitude where `1.0` plays the sound at its own volume.
+    pub velocity: f32,
+    /// The chance, from `0.0` to `1.0`, that the step plays when it is reached.
+    pub probability: f32,
+}
+
+impl Default for KiraStep {
+    fn default() -> Self {
+        Self {
+            active: false,
+            velocity: 1.0,
+            probability: 1.0,
+        }
+    }
+}
+
+impl KiraStep {
+    /// Creates an active step with full velocity and probability.
+    pub fn on() -> Self {
+        Self {
+            active: true,
+            ..default()
+        }
+    }
+
+    /// Creates an inactive step.
+    pub fn off() -> Self {
+        Self::default()
+    }
+}
+
+/// One row of a [`KiraSequencer`], a sound and the steps that it should be played on.
+#[derive(Debug, Clone)]
+pub struct KiraSequencerTrack {
+    /// The sound to play on every active step.
+    pub sound: Handle<KiraStaticSoundAsset>,
+    /// An optional entity with a [`KiraTrackHandle`] to play the sound on, if `None` the sound is
+    /// played on the main track.
+    ///
+    /// [`KiraTrackHandle`]: crate::KiraTrackHandle
+    pub track_entity: Option<Entity>,
+    /// The steps of the pattern. The pattern wraps around after the sequencer's `length`, missing
+    /// steps are treated as inactive.
+    pub steps: Vec<KiraStep>,
+}
+
+impl KiraSequencerTrack {
+    pub fn new(
+        sound: Handle<KiraStaticSoundAsset>,
+        track_entity: Option<Entity>,
+        steps: impl IntoIterator<Item = KiraStep>,
+    ) -> Self {
+        Self {
+            sound,
+            track_entity,
+            steps: steps.into_iter().collect(),
+        }
+    }
+}
+
+/// A step sequencer that plays the steps of its tracks in time with a clock entity, one step per
+/// tick.
+///
+/// Rather than reacting to clock ticks after they happen the sequencer schedules every step
+/// `look_ahead` ticks in advance with a clock based start time. Kira then starts the sounds with
+/// sample accuracy regardless of the frame rate, as long as a frame never takes longer than the
+/// look ahead window. Steps that are already in the past when they are scheduled are skipped with
+/// a warning rather than being played late.
+///
+/// Since steps are scheduled ahead of time edits to the pattern take effect once the steps already
+/// in the look ahead window have played.
+///
+/// A sequencer that starts while its clock is still on tick `0` plays the first step right away,
+/// otherwise it starts with the step of the clock's next tick. The same goes for a clock that is
+/// reset. Steps whose sound is not loaded yet when they are scheduled are skipped with a warning.
+///
+/// Sounds played by the sequencer are associated with the sequencer's entity through its
+/// [`KiraPlayingSounds`] component.
+///
+/// [`KiraPlayingSounds`]: crate::KiraPlayingSounds
+#[derive(Component, Debug, Clone)]
+pub struct KiraSequencer {
+    /// The entity holding the [`KiraClock`] that drives the sequencer.
+    ///
+    /// [`KiraClock`]: crate::KiraClock
+    pub clock: Entity,
+    /// The number of steps before the pattern repeats.
+    pub length: usize,
+    /// How far, as a fraction of a tick from `0.0` to `1.0`, every odd step is delayed.
+    pub swing: f64,
+    /// How many ticks ahead of the clock steps are scheduled.
+    pub look_ahead: u64,
+    /// The rows of the sequencer.
+    pub tracks: Vec<KiraSequencerTrack>,
+    // The next clock tick that has not been scheduled yet.
+    next_tick: Option<u64>,
+}
+
+impl KiraSequencer {
+    /// Creates a sequencer without any tracks driven by the clock on the `clock` entity.
+    pub fn new(clock: Entity, length: usize) -> Self {
+        Self {
+            clock,
+            length,
+            swing: 0.0,
+            look_ahead: 2,
+            tracks: vec![],
+            next_tick: None,
+        }
+    }
+
+    pub fn with_track(mut self, track: KiraSequencerTrack) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    pub fn with_swing(mut self, swing: f64) -> Self {
+        self.swing = swing;
+        self
+    }
+
+    pub fn with_look_ahead(mut self, look_ahead: u64) -> Self {
+        self.look_ahead = look_ahead;
+        self
+    }
+
+    /// The step that is played on the given clock tick.
+    pub fn step_at(&self, ticks: u64) -> usize {
+        (ticks % self.length.max(1) as u64) as usize
+    }
+
+    // The clock ticks whose steps have to be scheduled now that the clock is at `now`.
+    fn ticks_to_schedule(&mut self, eid: Entity, now: u64) -> RangeInclusive<u64> {
+        let until = now + self.look_ahead;
+        // Anything further ahead than the window means the clock was reset.
+        let next_tick = self.next_tick.filter(|next| *next <= until + 1);
+        // The current tick has already started so playing its step now would be late, except for
+        // the downbeat of a clock that was just started or reset.
+        let earliest = if next_tick.is_none() && now == 0 {
+            0
+        } else {
+            now + 1
+        };
+        let next = next_tick.unwrap_or(earliest);
+        if next < earliest {
+            warn!(
+                "Sequencer {:?} fell behind its clock, skipping {} steps. Consider increasing \
+                 the look ahead.",
+                eid,
+                earliest - next
+            );
+        }
+        self.next_tick = Some(until + 1);
+        next.max(earliest)..=until
+    }
+
+    // How far into its tick the given step starts.
+    fn swing_fraction(&self, step: usize) -> f64 {
+        if step % 2 == 1 {
+            self.swing.clamp(0.0, 0.99)
+        } else {
+            0.0
+        }
+    }
+}
+
+pub(crate) struct KiraSequencerPlugin;
+
+impl Plugin for KiraSequencerPlugin {
+    fn build(&self, app: &mut App) {
+        // Scheduling before the play events are consumed means the steps reach kira this frame.
+        app.add_systems(Update, schedule_steps_sys.before(do_play_sys));
+    }
+}
+
+fn schedule_steps_sys(
+    assets: Res<Assets<KiraStaticSoundAsset>>,
+    clocks: Query<&KiraClockHandle>,
+    mut sequencers: Query<(Entity, &mut KiraSequencer)>,
+    mut ev_play: EventWriter<KiraPlaySoundEvent>,
+) {
+    for (eid, mut sequencer) in sequencers.iter_mut() {
+        let Ok(clock) = clocks.get(sequencer.clock) else {
+            continue;
+        };
+        let clock = &clock.0;
+        if !clock.ticking() {
+            continue;
+        }
+        let ticks = sequencer
+            .bypass_change_detection()
+            .ticks_to_schedule(eid, clock.time().ticks);
+        let mut unloaded = 0;
+        for ticks in ticks {
+            let step = sequencer.step_at(ticks);
+            let start_time = ClockTime {
+                clock: clock.id(),
+                ticks,
+                fraction: sequencer.swing_fraction(step),
+            };
+            for track in sequencer.tracks.iter() {
+                let Some(step) = track.steps.get(step).filter(|step| step.active) else {
+                    continue;
+                };
+                if step.probability < 1.0 && fastrand::f32() >= step.probability {
+                    continue;
+                }
+                let Some(sound_asset) = assets.get(&track.sound) else {
+                    unloaded += 1;
+                    continue;
+                };
+                let mut sound = sound_asset.sound.clone();
+                sound.0.settings.start_time = start_time.into();
+                // The velocity scales the volume the sound already has.
+                let velocity = amplitude_to_decibels(step.velocity);
+                sound.0.settings.volume =
+                    map_value(sound.0.settings.volume, |volume| volume + velocity);
+                ev_play.write(KiraPlaySoundEvent::new(eid, track.track_entity, sound));
+            }
+        }
+        if unloaded > 0 {
+            warn!(
+                "Sequencer {:?} skipped {} steps whose sound is not loaded yet.",
+                eid, unloaded
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_line_up_with_clock_ticks() {
+        let mut sequencer = KiraSequencer::new(Entity::PLACEHOLDER, 4).with_swing(0.25);
+        let eid = Entity::PLACEHOLDER;
+        // The downbeat of a clock that was just started plays right away.
+        assert_eq!(sequencer.ticks_to_schedule(eid, 0), 0..=2);
+        assert!(sequencer.ticks_to_schedule(eid, 0).is_empty());
+        assert_eq!(sequencer.ticks_to_schedule(eid, 1), 3..=3);
+        assert_eq!(sequencer.ticks_to_schedule(eid, 3), 4..=5);
+        // Falling behind skips the steps that are already in the past.
+        assert_eq!(sequencer.ticks_to_schedule(eid, 6), 7..=8);
+        let steps: Vec<usize> = (3..=8).map(|ticks| sequencer.step_at(ticks)).collect();
+        assert_eq!(steps, vec![3, 0, 1, 2, 3, 0]);
+        // Only the off beats swing.
+        assert_eq!(sequencer.swing_fraction(0), 0.0);
+        assert_eq!(sequencer.swing_fraction(3), 0.25);
+    }
+
+    #[test]
+    fn clock_reset_starts_over_from_the_first_step() {
+        let mut sequencer = KiraSequencer::new(Entity::PLACEHOLDER, 4);
+        let eid = Entity::PLACEHOLDER;
+        assert_eq!(sequencer.ticks_to_schedule(eid, 0), 0..=2);
+        assert_eq!(sequencer.ticks_to_schedule(eid, 9), 10..=11);
+        // The clock was reset and is back on its first tick.
+        assert_eq!(sequencer.ticks_to_schedule(eid, 0), 0..=2);
+        assert_eq!(sequencer.step_at(0), 0);
+        // A reset noticed after the first tick went by continues with the next one.
+        assert_eq!(sequencer.ticks_to_schedule(eid, 9), 10..=11);
+        assert_eq!(sequencer.ticks_to_schedule(eid, 1), 2..=3);
+    }
+}
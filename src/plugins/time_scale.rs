@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use kira::{PlaybackRate, Tween};
+
+use crate::{KiraPlayingSounds, sound::sound_types::KiraPlayingSound};
+
+use super::events::{KiraVoice, do_play_sys};
+
+/// Makes audio follow the speed of Bevy's virtual time, for example for slow motion with
+/// `Time<Virtual>::set_relative_speed`.
+///
+/// Add this component to an entity with a [`KiraTrackHandle`] to scale the playback rate of every
+/// static sound played on that track through a [`KiraPlaySoundEvent`]. Use
+/// [`KiraPlaySoundEvent::ignore_time_scale`] for sounds that should keep their pitch, such as UI
+/// sounds. Only voices in a [`KiraPlayingSounds`] are scaled: sounds played on the main track and
+/// the music of a [`KiraMusicPlayer`], [`KiraMusicLayers`] or [`KiraMusicGraphPlayer`] keep their
+/// pitch, as do sounds played with a playback rate that follows a modulator.
+///
+/// Added to an entity with a [`KiraClock`] the clock's speed is scaled as well, so sequencers and
+/// music synced to the clock slow down with the game. The clock is paused while virtual time is
+/// stopped.
+///
+/// [`KiraTrackHandle`]: crate::KiraTrackHandle
+/// [`KiraPlaySoundEvent`]: crate::KiraPlaySoundEvent
+/// [`KiraPlaySoundEvent::ignore_time_scale`]: crate::KiraPlaySoundEvent::ignore_time_scale
+/// [`KiraClock`]: crate::KiraClock
+/// [`KiraMusicPlayer`]: crate::KiraMusicPlayer
+/// [`KiraMusicLayers`]: crate::KiraMusicLayers
+/// [`KiraMusicGraphPlayer`]: crate::KiraMusicGraphPlayer
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct KiraTimeScale;
+
+pub(crate) struct KiraTimeScalePlugin;
+
+impl Plugin for KiraTimeScalePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, time_scale_sounds_sys.after(do_play_sys));
+    }
+}
+
+fn time_scale_sounds_sys(
+    time: Res<Time<Virtual>>,
+    mut sounds: Query<&mut KiraPlayingSounds>,
+    scaled_tracks: Query<(), With<KiraTimeScale>>,
+) {
+    let speed = time.relative_speed_f64();
+    for mut sounds in sounds.iter_mut() {
+        // Rates that aren't fixed can't be scaled without replacing them.
+        let outdated = |voice: &KiraVoice| {
+            voice.time_scaled
+                && voice.playback_rate.is_some()
+                && voice.time_scale != speed
+                && voice
+                    .track
+                    .is_some_and(|track| scaled_tracks.contains(track))
+        };
+        // Look for voices to update first to avoid triggering change detection every frame.
+        if !sounds.0.iter().any(outdated) {
+            continue;
+        }
+        for voice in sounds.0.iter_mut().filter(|voice| outdated(voice)) {
+            if let (KiraPlayingSound::Static(sound), Some(rate)) =
+                (&mut voice.sound, voice.playback_rate)
+            {
+                sound.set_playback_rate(PlaybackRate(rate * speed), Tween::default());
+            }
+            voice.time_scale = speed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kira::{
+        AudioManager, AudioManagerSettings, Decibels, Frame,
+        backend::mock::MockBackend,
+        sound::static_sound::{StaticSoundData, StaticSoundSettings},
+    };
+
+    use super::*;
+
+    fn voice(
+        manager: &mut AudioManager<MockBackend>,
+        track: Option<Entity>,
+        playback_rate: Option<f64>,
+    ) -> KiraVoice {
+        let sound = StaticSoundData {
+            sample_rate: 100,
+            frames: vec![Frame::ZERO; 100].into(),
+            settings: StaticSoundSettings::default(),
+            slice: None,
+        };
+        KiraVoice {
+            sound: KiraPlayingSound::Static(manager.play(sound).unwrap()),
+            track,
+            group: None,
+            priority: 0,
+            volume: Decibels::IDENTITY,
+            playback_rate,
+            time_scaled: true,
+            time_scale: 1.0,
+            serial: 0,
+            stolen: false,
+            paused: false,
+        }
+    }
+
+    #[test]
+    fn only_fixed_rates_on_scaled_tracks_follow_the_time_scale() {
+        let mut manager =
+            AudioManager::<MockBackend>::new(AudioManagerSettings::default()).unwrap();
+        let mut app = App::new();
+        app.add_systems(Update, time_scale_sounds_sys);
+        let mut time = Time::<Virtual>::default();
+        time.set_relative_speed_f64(0.5);
+        app.insert_resource(time);
+        let scaled = app.world_mut().spawn(KiraTimeScale).id();
+        let unscaled = app.world_mut().spawn_empty().id();
+        let mut ignoring = voice(&mut manager, Some(scaled), Some(1.0));
+        ignoring.time_scaled = false;
+        let voices = vec![
+            voice(&mut manager, Some(scaled), Some(1.0)),
+            // The rate follows a modulator.
+            voice(&mut manager, Some(scaled), None),
+            voice(&mut manager, Some(unscaled), Some(1.0)),
+            voice(&mut manager, None, Some(1.0)),
+            ignoring,
+        ];
+        let sounds = app.world_mut().spawn(KiraPlayingSounds(voices)).id();
+        app.update();
+        let sounds = app.world().get::<KiraPlayingSounds>(sounds).unwrap();
+        let time_scales: Vec<f64> = sounds.0.iter().map(|voice| voice.time_scale).collect();
+        assert_eq!(time_scales, vec![0.5, 1.0, 1.0, 1.0, 1.0]);
+    }
+}
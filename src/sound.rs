@@ -1,2 +1,4 @@
+pub(crate) mod container;
+pub(crate) mod engine;
 pub(crate) mod sound_types;
 pub(crate) mod static_sounds;
@@ -0,0 +1,186 @@
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use kira::{Decibels, PlaybackRate, sound::static_sound::StaticSoundData};
+
+use crate::util::map_value;
+
+use super::static_sounds::KiraStaticSoundAsset;
+
+/// How a [`KiraSoundContainer`] picks the sound to play.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KiraContainerMode {
+    /// Any of the sounds, the same sound may play several times in a row.
+    Random,
+    /// Any of the sounds except the one that played last.
+    #[default]
+    RandomNoRepeat,
+    /// The sounds in order, starting over after the last one.
+    Sequential,
+    /// Every sound once in a random order before any of them repeats.
+    Shuffle,
+}
+
+/// A group of interchangeable sounds, for example several footstep recordings, of which one is
+/// picked every time the container is played. Each play can also randomize the volume and playback
+/// rate of the sound so repeated sounds don't feel mechanical.
+///
+/// Containers are assets, add them to `Assets<KiraSoundContainer>` and play them with
+/// [`KiraPlaySoundEvent::from_container`]. The state used to avoid repetitions is tracked per
+/// container asset.
+///
+/// [`KiraPlaySoundEvent::from_container`]: crate::KiraPlaySoundEvent::from_container
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct KiraSoundContainer {
+    /// The sounds to pick from.
+    pub sounds: Vec<Handle<KiraStaticSoundAsset>>,
+    pub mode: KiraContainerMode,
+    /// The range, in decibels, of the offset added to the volume of every play.
+    pub volume: RangeInclusive<f32>,
+    /// The range of the factor the playback rate of every play is multiplied by, `1.0` keeps the
+    /// sound's own pitch.
+    pub playback_rate: RangeInclusive<f64>,
+}
+
+impl KiraSoundContainer {
+    pub fn new(
+        sounds: impl IntoIterator<Item = Handle<KiraStaticSoundAsset>>,
+        mode: KiraContainerMode,
+    ) -> Self {
+        Self {
+            sounds: sounds.into_iter().collect(),
+            mode,
+            volume: 0.0..=0.0,
+            playback_rate: 1.0..=1.0,
+        }
+    }
+
+    pub fn with_volume(mut self, volume: RangeInclusive<f32>) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn with_playback_rate(mut self, playback_rate: RangeInclusive<f64>) -> Self {
+        self.playback_rate = playback_rate;
+        self
+    }
+
+    // Applies the volume and playback rate randomization to a sound picked from the container.
+    pub(crate) fn randomize(&self, sound: &mut StaticSoundData) {
+        let (min, max) = (*self.volume.start(), *self.volume.end());
+        let volume = min + fastrand::f32() * (max - min);
+        let (min, max) = (*self.playback_rate.start(), *self.playback_rate.end());
+        let playback_rate = min + fastrand::f64() * (max - min);
+        // Applied on top of the sound's own settings, which may follow a modulator.
+        sound.settings.volume = map_value(sound.settings.volume, |v| v + Decibels(volume));
+        sound.settings.playback_rate = map_value(sound.settings.playback_rate, |rate| {
+            PlaybackRate(rate.0 * playback_rate)
+        });
+    }
+}
+
+/// Remembers what a container played so the next pick can respect its mode.
+#[derive(Debug, Default)]
+pub(crate) struct KiraContainerState {
+    last: Option<usize>,
+    // The indices left to play in shuffle mode, in reverse order.
+    bag: Vec<usize>,
+}
+
+impl KiraContainerState {
+    /// Picks the index of the next sound to play from `container`.
+    pub(crate) fn pick(&mut self, container: &KiraSoundContainer) -> Option<usize> {
+        let len = container.sounds.len();
+        if len == 0 {
+            return None;
+        }
+        let index = match (container.mode, self.last) {
+            (KiraContainerMode::RandomNoRepeat, Some(last)) if len > 1 => {
+                let index = fastrand::usize(..len - 1);
+                if index >= last { index + 1 } else { index }
+            }
+            (KiraContainerMode::Random | KiraContainerMode::RandomNoRepeat, _) => {
+                fastrand::usize(..len)
+            }
+            (KiraContainerMode::Sequential, Some(last)) => (last + 1) % len,
+            (KiraContainerMode::Sequential, None) => 0,
+            (KiraContainerMode::Shuffle, last) => {
+                // The container may have been edited since the bag was filled.
+                self.bag.retain(|index| *index < len);
+                if self.bag.is_empty() {
+                    self.bag = (0..len).collect();
+                    fastrand::shuffle(&mut self.bag);
+                    // Don't let the last sound of a round start the next one.
+                    if len > 1 && self.bag.last() == last.as_ref() {
+                        self.bag.swap(0, len - 1);
+                    }
+                }
+                self.bag.pop()?
+            }
+        };
+        self.last = Some(index);
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(len: usize, mode: KiraContainerMode) -> KiraSoundContainer {
+        KiraSoundContainer::new((0..len).map(|_| Handle::default()), mode)
+    }
+
+    fn picks(container: &KiraSoundContainer, count: usize) -> Vec<usize> {
+        let mut state = KiraContainerState::default();
+        (0..count).map(|_| state.pick(container).unwrap()).collect()
+    }
+
+    #[test]
+    fn empty_container_picks_nothing() {
+        let container = container(0, KiraContainerMode::Random);
+        assert_eq!(KiraContainerState::default().pick(&container), None);
+    }
+
+    #[test]
+    fn sequential_starts_over_after_the_last_sound() {
+        let container = container(3, KiraContainerMode::Sequential);
+        assert_eq!(picks(&container, 5), vec![0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn random_no_repeat_never_repeats() {
+        let container = container(3, KiraContainerMode::RandomNoRepeat);
+        let picks = picks(&container, 200);
+        assert!(picks.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn random_no_repeat_with_a_single_sound_repeats_it() {
+        let container = container(1, KiraContainerMode::RandomNoRepeat);
+        assert_eq!(picks(&container, 2), vec![0, 0]);
+    }
+
+    #[test]
+    fn shuffle_plays_every_sound_once_per_round() {
+        let container = container(4, KiraContainerMode::Shuffle);
+        let picks = picks(&container, 40);
+        for round in picks.chunks(4) {
+            let mut round = round.to_vec();
+            round.sort();
+            assert_eq!(round, vec![0, 1, 2, 3]);
+        }
+        assert!(picks.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn shuffle_forgets_removed_sounds() {
+        let mut container = container(4, KiraContainerMode::Shuffle);
+        let mut state = KiraContainerState::default();
+        state.pick(&container);
+        container.sounds.truncate(2);
+        for _ in 0..10 {
+            assert!(state.pick(&container).unwrap() < 2);
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    f32::consts::FRAC_PI_2,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use kira::{
+    Decibels, Frame, Tween,
+    info::Info,
+    sound::{PlaybackState, Sound, SoundData, static_sound::StaticSoundData},
+};
+
+use super::{sound_types::DynamicSoundHandle, static_sounds::KiraError};
+
+// The playback rates a layer is pitched within, so a layer far from the current RPM doesn't alias
+// or slow to a crawl.
+const MIN_RATE: f64 = 0.25;
+const MAX_RATE: f64 = 4.0;
+// How long volume changes take, short enough to feel immediate without clicking.
+const VOLUME_SMOOTHING: f64 = 0.01;
+// How long the sound fades out for when it is stopped or paused.
+const STOP_FADE: f64 = 0.05;
+
+/// A recording of an engine running at a steady RPM, one of the layers of
+/// a [`KiraEngineSoundData`].
+#[derive(Debug, Clone)]
+pub struct KiraEngineLayer {
+    /// The RPM the engine ran at in the recording.
+    pub rpm: f32,
+    /// The recording, looped from start to end. Its settings and slice are ignored.
+    pub sound: StaticSoundData,
+}
+
+/// An engine or vehicle sound that follows an RPM as a layered crossfade. Each layer is a looping
+/// recording made at a given RPM. The two layers closest to the current RPM are crossfaded with
+/// equal power and every layer is pitched by the ratio of the current RPM to its own, within a
+/// quarter and four times its speed, so the sound rises smoothly between the recordings. The
+/// recordings are resampled as a whole rather than cut into grains, so record the layers close
+/// enough together that the pitch shift between two of them stays natural.
+///
+/// Play it with a [`KiraPlaySoundEvent`] like any other sound and drive it through the
+/// [`KiraEngineSoundHandle`] found in the entity's [`KiraPlayingSounds`]. The sound plays until it
+/// is stopped through its handle.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_mod_kira::{
+/// #     KiraEngineSoundData, KiraEngineSoundHandle, KiraPlaySoundEvent, KiraPlayingSounds,
+/// #     KiraStaticSoundAsset,
+/// # };
+/// #[derive(Component)]
+/// struct Car {
+///     rpm: f32,
+///     idle: Handle<KiraStaticSoundAsset>,
+///     high: Handle<KiraStaticSoundAsset>,
+/// }
+///
+/// fn start_engine_sys(
+///     cars: Query<(Entity, &Car), Without<KiraPlayingSounds>>,
+///     assets: Res<Assets<KiraStaticSoundAsset>>,
+///     mut ev_play: EventWriter<KiraPlaySoundEvent>,
+/// ) {
+///     for (eid, car) in cars.iter() {
+///         let (Some(idle), Some(high)) = (assets.get(&car.idle), assets.get(&car.high)) else {
+///             continue;
+///         };
+///         let engine = KiraEngineSoundData::new(car.rpm)
+///             .with_layer(800.0, idle.sound.0.clone())
+///             .with_layer(4000.0, high.sound.0.clone());
+///         ev_play.write(KiraPlaySoundEvent::new(eid, None, engine));
+///     }
+/// }
+///
+/// fn engine_rpm_sys(cars: Query<(&Car, &KiraPlayingSounds)>) {
+///     for (car, sounds) in cars.iter() {
+///         for engine in sounds.dynamic_handles::<KiraEngineSoundHandle>() {
+///             engine.set_rpm(car.rpm);
+///         }
+///     }
+/// }
+/// ```
+///
+/// [`KiraPlaySoundEvent`]: crate::KiraPlaySoundEvent
+/// [`KiraPlayingSounds`]: crate::KiraPlayingSounds
+#[derive(Debug, Clone)]
+pub struct KiraEngineSoundData {
+    pub layers: Vec<KiraEngineLayer>,
+    /// The RPM the sound starts at.
+    pub rpm: f32,
+    pub volume: Decibels,
+    /// How long the sound takes to follow a new RPM, which hides the steps between frames.
+    pub smoothing: Duration,
+}
+
+impl KiraEngineSoundData {
+    pub fn new(rpm: f32) -> Self {
+        Self {
+            layers: Vec::new(),
+            rpm,
+            volume: Decibels::IDENTITY,
+            smoothing: Duration::from_millis(50),
+        }
+    }
+
+    /// Adds a recording made at `rpm`.
+    pub fn with_layer(mut self, rpm: f32, sound: StaticSoundData) -> Self {
+        self.layers.push(KiraEngineLayer { rpm, sound });
+        self
+    }
+
+    pub fn with_volume(mut self, volume: Decibels) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn with_smoothing(mut self, smoothing: Duration) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+}
+
+impl SoundData for KiraEngineSoundData {
+    type Error = KiraError;
+    type Handle = KiraEngineSoundHandle;
+
+    fn into_sound(self) -> Result<(Box<dyn Sound>, Self::Handle), Self::Error> {
+        let mut layers: Vec<_> = self
+            .layers
+            .into_iter()
+            .filter(|layer| !layer.sound.frames.is_empty() && layer.rpm > 0.0)
+            .map(|layer| EngineLayer {
+                rpm: layer.rpm as f64,
+                sample_rate: layer.sound.sample_rate as f64,
+                frames: layer.sound.frames,
+                position: 0.0,
+            })
+            .collect();
+        if layers.is_empty() {
+            return Err(KiraError::EmptyEngineSound);
+        }
+        layers.sort_by(|a, b| a.rpm.total_cmp(&b.rpm));
+        let shared = Arc::new(EngineShared {
+            rpm: AtomicU32::new(self.rpm.to_bits()),
+            volume: AtomicU32::new(self.volume.0.to_bits()),
+            stopping: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+        });
+        Ok((
+            Box::new(EngineSound {
+                shared: shared.clone(),
+                layers,
+                rpm: self.rpm as f64,
+                smoothing: self.smoothing.as_secs_f64(),
+                volume: self.volume.as_amplitude(),
+                fade: 1.0,
+                pause_fade: 1.0,
+            }),
+            KiraEngineSoundHandle { shared },
+        ))
+    }
+}
+
+/// Controls a playing [`KiraEngineSoundData`]. Every method takes `&self` so the handle can be
+/// used straight from [`KiraPlayingSounds::dynamic_handles`].
+///
+/// [`KiraPlayingSounds::dynamic_handles`]: crate::KiraPlayingSounds::dynamic_handles
+#[derive(Debug)]
+pub struct KiraEngineSoundHandle {
+    shared: Arc<EngineShared>,
+}
+
+impl KiraEngineSoundHandle {
+    /// Sets the RPM the sound moves to over its smoothing time.
+    pub fn set_rpm(&self, rpm: f32) {
+        self.shared.rpm.store(rpm.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The RPM last set.
+    pub fn rpm(&self) -> f32 {
+        f32::from_bits(self.shared.rpm.load(Ordering::Relaxed))
+    }
+
+    pub fn set_volume(&self, volume: Decibels) {
+        self.shared
+            .volume
+            .store(volume.0.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Fades the sound out and stops it.
+    pub fn stop(&self) {
+        self.shared.stopping.store(true, Ordering::Relaxed);
+    }
+}
+
+impl DynamicSoundHandle for KiraEngineSoundHandle {
+    fn state(&self) -> PlaybackState {
+        if self.shared.finished.load(Ordering::Relaxed) {
+            PlaybackState::Stopped
+        } else if self.shared.stopping.load(Ordering::Relaxed) {
+            PlaybackState::Stopping
+        } else if self.shared.paused.load(Ordering::Relaxed) {
+            PlaybackState::Paused
+        } else {
+            PlaybackState::Playing
+        }
+    }
+
+    // The sound fades over its own short fade rather than the tween.
+    fn pause(&mut self, _tween: Tween) {
+        self.shared.paused.store(true, Ordering::Relaxed);
+    }
+
+    fn resume(&mut self, _tween: Tween) {
+        self.shared.paused.store(false, Ordering::Relaxed);
+    }
+}
+
+// The values set through the handle, floats are stored as their bits.
+#[derive(Debug)]
+struct EngineShared {
+    rpm: AtomicU32,
+    volume: AtomicU32,
+    stopping: AtomicBool,
+    paused: AtomicBool,
+    finished: AtomicBool,
+}
+
+struct EngineLayer {
+    rpm: f64,
+    sample_rate: f64,
+    frames: Arc<[Frame]>,
+    // The position in frames of the recording, fractional between two frames.
+    position: f64,
+}
+
+impl EngineLayer {
+    fn frame(&self) -> Frame {
+        let len = self.frames.len();
+        let index = self.position as usize;
+        let amount = (self.position - index as f64) as f32;
+        let current = self.frames[index % len];
+        let next = self.frames[(index + 1) % len];
+        current + (next - current) * amount
+    }
+
+    // The playback rate that pitches the recording to the given RPM.
+    fn rate(&self, rpm: f64) -> f64 {
+        (rpm / self.rpm).clamp(MIN_RATE, MAX_RATE)
+    }
+
+    fn advance(&mut self, rpm: f64, dt: f64) {
+        let rate = self.rate(rpm);
+        self.position = (self.position + dt * self.sample_rate * rate) % self.frames.len() as f64;
+    }
+}
+
+struct EngineSound {
+    shared: Arc<EngineShared>,
+    // Sorted by RPM.
+    layers: Vec<EngineLayer>,
+    // The RPM and amplitude currently played, following the values set through the handle.
+    rpm: f64,
+    smoothing: f64,
+    volume: f32,
+    // Goes from `1.0` to `0.0` once the sound is stopped.
+    fade: f64,
+    // Moves towards `0.0` while the sound is paused and back to `1.0` once it is resumed.
+    pause_fade: f32,
+}
+
+impl EngineSound {
+    // The layers surrounding the current RPM and how far the RPM is from the lower to the upper one.
+    fn blend(&self) -> (usize, usize, f32) {
+        let upper = self.layers.partition_point(|layer| layer.rpm <= self.rpm);
+        if upper == 0 {
+            return (0, 0, 0.0);
+        }
+        let lower = upper - 1;
+        if upper == self.layers.len() {
+            return (lower, lower, 0.0);
+        }
+        let (low, high) = (self.layers[lower].rpm, self.layers[upper].rpm);
+        (lower, upper, ((self.rpm - low) / (high - low)) as f32)
+    }
+}
+
+// The factor moving a smoothed value towards its target in one step of `dt`.
+fn smoothing_factor(dt: f64, duration: f64) -> f64 {
+    if duration > 0.0 {
+        1.0 - (-dt / duration).exp()
+    } else {
+        1.0
+    }
+}
+
+impl Sound for EngineSound {
+    fn process(&mut self, out: &mut [Frame], dt: f64, _info: &Info) {
+        let target_rpm = f32::from_bits(self.shared.rpm.load(Ordering::Relaxed)) as f64;
+        let target_volume =
+            Decibels(f32::from_bits(self.shared.volume.load(Ordering::Relaxed))).as_amplitude();
+        let stopping = self.shared.stopping.load(Ordering::Relaxed);
+        let paused = if self.shared.paused.load(Ordering::Relaxed) {
+            0.0
+        } else {
+            1.0
+        };
+        let pause_factor = smoothing_factor(dt, STOP_FADE) as f32;
+        let rpm_factor = smoothing_factor(dt, self.smoothing);
+        let volume_factor = smoothing_factor(dt, VOLUME_SMOOTHING) as f32;
+        for frame in out.iter_mut() {
+            if stopping {
+                self.fade -= dt / STOP_FADE;
+                if self.fade <= 0.0 {
+                    self.fade = 0.0;
+                    self.shared.finished.store(true, Ordering::Relaxed);
+                }
+            }
+            self.rpm += (target_rpm - self.rpm) * rpm_factor;
+            self.volume += (target_volume - self.volume) * volume_factor;
+            self.pause_fade += (paused - self.pause_fade) * pause_factor;
+            // Equal power crossfade so the loudness stays the same between the two layers.
+            let (lower, upper, amount) = self.blend();
+            let mut mixed = self.layers[lower].frame() * (amount * FRAC_PI_2).cos();
+            if upper != lower {
+                mixed += self.layers[upper].frame() * (amount * FRAC_PI_2).sin();
+            }
+            *frame = mixed * self.volume * self.fade as f32 * self.pause_fade;
+            // Every layer keeps running so it comes back in where it would be.
+            for layer in self.layers.iter_mut() {
+                layer.advance(self.rpm, dt);
+            }
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.shared.finished.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kira::sound::static_sound::StaticSoundSettings;
+
+    use super::*;
+
+    fn recording(frames: usize) -> StaticSoundData {
+        StaticSoundData {
+            sample_rate: 100,
+            frames: vec![Frame::ZERO; frames].into(),
+            settings: StaticSoundSettings::default(),
+            slice: None,
+        }
+    }
+
+    fn layer(rpm: f64) -> EngineLayer {
+        EngineLayer {
+            rpm,
+            sample_rate: 100.0,
+            frames: recording(1000).frames,
+            position: 0.0,
+        }
+    }
+
+    fn sound(rpm: f64) -> EngineSound {
+        EngineSound {
+            shared: Arc::new(EngineShared {
+                rpm: AtomicU32::new((rpm as f32).to_bits()),
+                volume: AtomicU32::new(0.0f32.to_bits()),
+                stopping: AtomicBool::new(false),
+                paused: AtomicBool::new(false),
+                finished: AtomicBool::new(false),
+            }),
+            layers: vec![layer(1000.0), layer(2000.0), layer(4000.0)],
+            rpm,
+            smoothing: 0.0,
+            volume: 1.0,
+            fade: 1.0,
+            pause_fade: 1.0,
+        }
+    }
+
+    #[test]
+    fn blend_picks_the_surrounding_layers() {
+        assert_eq!(sound(1500.0).blend(), (0, 1, 0.5));
+        assert_eq!(sound(2000.0).blend(), (1, 2, 0.0));
+        assert_eq!(sound(3000.0).blend(), (1, 2, 0.5));
+        // Outside the recorded range only the closest layer plays.
+        assert_eq!(sound(500.0).blend(), (0, 0, 0.0));
+        assert_eq!(sound(6000.0).blend(), (2, 2, 0.0));
+    }
+
+    #[test]
+    fn smoothing_factor_follows_the_duration() {
+        assert_eq!(smoothing_factor(0.01, 0.0), 1.0);
+        // After one time constant the value has moved about 63% of the way.
+        assert!((smoothing_factor(0.05, 0.05) - (1.0 - (-1.0f64).exp())).abs() < 1e-12);
+        assert!(smoothing_factor(0.001, 0.05) < smoothing_factor(0.01, 0.05));
+    }
+
+    #[test]
+    fn layer_pitch_is_clamped() {
+        let mut layer = layer(1000.0);
+        assert_eq!(layer.rate(1500.0), 1.5);
+        assert_eq!(layer.rate(100.0), MIN_RATE);
+        assert_eq!(layer.rate(10000.0), MAX_RATE);
+        layer.advance(10000.0, 0.5);
+        assert_eq!(layer.position, 0.5 * 100.0 * MAX_RATE);
+    }
+
+    #[test]
+    fn sound_without_usable_layers_is_an_error() {
+        let empty = KiraEngineSoundData::new(1000.0)
+            .with_layer(1000.0, recording(0))
+            .with_layer(0.0, recording(100));
+        assert!(matches!(
+            empty.into_sound(),
+            Err(KiraError::EmptyEngineSound)
+        ));
+        let engine = KiraEngineSoundData::new(1000.0).with_layer(1000.0, recording(100));
+        assert!(engine.into_sound().is_ok());
+    }
+}
@@ -3,13 +3,24 @@ use std::any::Any;
 use anyhow::{Error, anyhow};
 use bevy::ecs::component::Component;
 use kira::{
-    sound::{PlaybackState, SoundData, static_sound::StaticSoundHandle},
-    track::{MainTrackHandle, TrackHandle},
+    Decibels, StartTime, Tween, Value,
+    sound::{
+        PlaybackState, SoundData,
+        static_sound::{StaticSoundData, StaticSoundHandle, StaticSoundSettings},
+    },
+    track::{MainTrackHandle, SendTrackHandle, TrackHandle},
 };
 
+use super::static_sounds::KiraStaticSoundData;
+use crate::KiraTrackVolume;
+
 #[derive(Component)]
+#[require(KiraTrackVolume)]
 pub struct KiraTrackHandle(pub TrackHandle);
 
+#[derive(Component)]
+pub struct KiraSendTrackHandle(pub SendTrackHandle);
+
 /// KiraPlayable is a trait that allows KiraPlugin to play static (sounds loaded from a supported
 /// sound file) and dynamic sounds implementations of `kira::sound::Sound`.
 ///
@@ -24,6 +35,34 @@ pub struct KiraTrackHandle(pub TrackHandle);
 pub trait KiraPlayable: Send + Sync + 'static {
     fn play_in_track(&self, track: &mut KiraTrackHandle) -> Result<KiraPlayingSound, Error>;
     fn play_in_main_track(&self, track: &mut MainTrackHandle) -> Result<KiraPlayingSound, Error>;
+
+    /// Sets when the sound should start playing. This is used by `KiraPlugin` to apply the start
+    /// time of a [`KiraPlaySoundEvent`]. The blanket implementation supports static sounds, other
+    /// sounds return an error.
+    ///
+    /// [`KiraPlaySoundEvent`]: crate::KiraPlaySoundEvent
+    fn set_start_time(&mut self, _start_time: StartTime) -> Result<(), Error> {
+        Err(anyhow!(
+            "{} does not support setting a start time",
+            std::any::type_name::<Self>()
+        ))
+    }
+
+    /// The volume the sound will play at, if known. This is used by `KiraPlugin` to find the
+    /// quietest voice when voices are limited, see [`KiraVoicePolicy::StealQuietest`].
+    ///
+    /// [`KiraVoicePolicy::StealQuietest`]: crate::KiraVoicePolicy::StealQuietest
+    fn volume(&self) -> Option<Decibels> {
+        None
+    }
+
+    /// The playback rate the sound will play at, if known. This is used by `KiraPlugin` to scale
+    /// the playback rate of sounds on tracks with a [`KiraTimeScale`].
+    ///
+    /// [`KiraTimeScale`]: crate::KiraTimeScale
+    fn playback_rate(&self) -> Option<f64> {
+        None
+    }
 }
 
 pub trait Downcastable: Any + Send + Sync {
@@ -43,6 +82,15 @@ pub trait DynamicSoundHandle: Downcastable {
     /// `PlaybackState::Stopped` if a sound is finished and ready to be cleaned up else a non
     /// Stopped state should be returned.
     fn state(&self) -> PlaybackState;
+
+    /// Pauses the sound with a fade. This is used by [`KiraAudioPause`] for sounds playing on the
+    /// main track, sounds that can't be paused keep playing.
+    ///
+    /// [`KiraAudioPause`]: crate::KiraAudioPause
+    fn pause(&mut self, _tween: Tween) {}
+
+    /// Resumes the sound after [`DynamicSoundHandle::pause`].
+    fn resume(&mut self, _tween: Tween) {}
 }
 
 pub enum KiraPlayingSound {
@@ -81,4 +129,43 @@ where
         res.map_err(|_e| anyhow!("failed to play sound: {}", std::any::type_name::<D>()))
             .map(|handle| handle.into())
     }
+
+    fn set_start_time(&mut self, start_time: StartTime) -> Result<(), Error> {
+        let any: &mut dyn Any = self;
+        if let Some(data) = any.downcast_mut::<KiraStaticSoundData>() {
+            data.0.settings.start_time = start_time;
+        } else if let Some(data) = any.downcast_mut::<StaticSoundData>() {
+            data.settings.start_time = start_time;
+        } else {
+            return Err(anyhow!(
+                "{} does not support setting a start time",
+                std::any::type_name::<D>()
+            ));
+        }
+        Ok(())
+    }
+
+    fn volume(&self) -> Option<Decibels> {
+        match static_settings(self)?.volume {
+            Value::Fixed(volume) => Some(volume),
+            _ => None,
+        }
+    }
+
+    fn playback_rate(&self) -> Option<f64> {
+        match static_settings(self)?.playback_rate {
+            Value::Fixed(rate) => Some(rate.0),
+            _ => None,
+        }
+    }
+}
+
+// The settings of a static sound, or `None` if `sound` is not a static sound.
+fn static_settings(sound: &dyn Any) -> Option<&StaticSoundSettings> {
+    if let Some(data) = sound.downcast_ref::<KiraStaticSoundData>() {
+        return Some(&data.0.settings);
+    }
+    sound
+        .downcast_ref::<StaticSoundData>()
+        .map(|data| &data.settings)
 }
@@ -14,6 +14,12 @@ pub enum KiraError {
     IoError(#[from] std::io::Error),
     #[error("An error occurred when parsing the file")]
     FromFileError(#[from] FromFileError),
+    #[error("An error occurred when parsing the RON file")]
+    RonError(#[from] ron::error::SpannedError),
+    #[error("The music graph is invalid: {0}")]
+    InvalidMusicGraph(String),
+    #[error("The engine sound has no layer with audio")]
+    EmptyEngineSound,
 }
 
 #[derive(TypePath, Clone, Asset)]
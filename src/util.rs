@@ -1,4 +1,9 @@
-use bevy::time::Timer;
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::{Component, Entity, Query, RemovedComponents, Without},
+    time::Timer,
+};
+use kira::{Decibels, Value};
 
 pub(crate) struct TimerMs<const N: i32> {
     pub(crate) timer: Timer,
@@ -11,3 +16,44 @@ impl<const N: i32> Default for TimerMs<N> {
         }
     }
 }
+
+/// Converts a linear amplitude (where `1.0` leaves the volume unchanged) into kira decibels, values
+/// at or below zero map to silence.
+pub(crate) fn amplitude_to_decibels(amplitude: f32) -> Decibels {
+    if amplitude <= 0.0 {
+        return Decibels::SILENCE;
+    }
+    Decibels((20.0 * amplitude.log10()).max(Decibels::SILENCE.0))
+}
+
+/// Applies `f` to a value, or to both ends of its mapping when the value follows a modulator or the
+/// listener distance.
+pub(crate) fn map_value<T: Copy>(value: Value<T>, f: impl Fn(T) -> T) -> Value<T> {
+    match value {
+        Value::Fixed(value) => Value::Fixed(f(value)),
+        Value::FromModulator { id, mut mapping } => {
+            mapping.output_range = (f(mapping.output_range.0), f(mapping.output_range.1));
+            Value::FromModulator { id, mapping }
+        }
+        Value::FromListenerDistance(mut mapping) => {
+            mapping.output_range = (f(mapping.output_range.0), f(mapping.output_range.1));
+            Value::FromListenerDistance(mapping)
+        }
+    }
+}
+
+/// The entities that lost their `T` component since the system last ran. Entities that were
+/// despawned since, or got the component back, are left out so only the ones that need cleaning
+/// up are returned.
+#[derive(SystemParam)]
+pub(crate) struct Removed<'w, 's, T: Component> {
+    removed: RemovedComponents<'w, 's, T>,
+    without: Query<'w, 's, (), Without<T>>,
+}
+
+impl<T: Component> Removed<'_, '_, T> {
+    pub(crate) fn read(&mut self) -> impl Iterator<Item = Entity> + '_ {
+        let Self { removed, without } = self;
+        removed.read().filter(|eid| without.contains(*eid))
+    }
+}
@@ -0,0 +1,18 @@
+use bevy::{prelude::*, state::app::StatesPlugin};
+use bevy_mod_kira::KiraPlugin;
+
+// Bevy only reports conflicting queries and system params when the schedules first run, so run
+// the plugin for a few frames. Without an audio device the context logs an error and carries on.
+#[test]
+fn plugin_runs() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        StatesPlugin,
+        KiraPlugin,
+    ));
+    for _ in 0..3 {
+        app.update();
+    }
+}